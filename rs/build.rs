@@ -0,0 +1,106 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+// Generates the Rust code for the built-in languages from languages.toml;
+// configfile.rs includes the result in initial_data_for_lang().
+
+use serde::Deserialize;
+use std::{collections::BTreeMap, env, fmt::Write, fs, path::Path};
+
+const LANGUAGES: &str = "languages.toml";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Lang {
+    name: String,
+    #[serde(default)]
+    exts: Vec<String>,
+    #[serde(default)]
+    filenames: Vec<String>,
+    #[serde(default)]
+    interpreters: Vec<String>,
+    #[serde(default)]
+    line_comments: Vec<String>,
+    #[serde(default)]
+    block_comments: Vec<(String, String)>,
+    #[serde(default)]
+    nested_comments: Vec<(String, String)>,
+    strings: Option<Vec<(String, String)>>,
+    #[serde(default)]
+    raw_strings: Vec<(String, String)>,
+    #[serde(default)]
+    multiline_strings: Vec<String>,
+    #[serde(default)]
+    char_literals: bool,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={LANGUAGES}");
+    let text = fs::read_to_string(LANGUAGES)
+        .unwrap_or_else(|err| panic!("failed to read {LANGUAGES}: {err}"));
+    let langs: BTreeMap<String, Lang> = toml::from_str(&text)
+        .unwrap_or_else(|err| panic!("invalid {LANGUAGES}: {err}"));
+    let mut code = String::from("HashMap::from([\n");
+    for (lang, data) in &langs {
+        write_lang(&mut code, lang, data);
+    }
+    code.push_str("])\n");
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+    fs::write(Path::new(&out_dir).join("languages.rs"), code)
+        .expect("failed to write languages.rs");
+}
+
+fn write_lang(code: &mut String, lang: &str, data: &Lang) {
+    let _ = write!(
+        code,
+        "    ({lang:?}.to_string(), LangData::new({:?}, \
+         HashSet::from({:?}), &{:?}, &{})",
+        data.name,
+        data.exts,
+        data.line_comments,
+        pairs(&data.block_comments)
+    );
+    if !data.nested_comments.is_empty() {
+        let _ = write!(
+            code,
+            ".with_nested_comments(&{})",
+            pairs(&data.nested_comments)
+        );
+    }
+    if let Some(strings) = &data.strings {
+        let _ = write!(code, ".with_strings(&{})", pairs(strings));
+    }
+    if !data.raw_strings.is_empty() {
+        let _ = write!(
+            code,
+            ".with_raw_strings(&{})",
+            pairs(&data.raw_strings)
+        );
+    }
+    if !data.multiline_strings.is_empty() {
+        let _ = write!(
+            code,
+            ".with_multiline_strings(&{:?})",
+            data.multiline_strings
+        );
+    }
+    if data.char_literals {
+        code.push_str(".with_char_literals()");
+    }
+    if !data.interpreters.is_empty() {
+        let _ =
+            write!(code, ".with_interpreters(&{:?})", data.interpreters);
+    }
+    if !data.filenames.is_empty() {
+        let _ = write!(code, ".with_filenames(&{:?})", data.filenames);
+    }
+    code.push_str("),\n");
+}
+
+fn pairs(pairs: &[(String, String)]) -> String {
+    let pairs: Vec<String> = pairs
+        .iter()
+        .map(|(start, end)| format!("({start:?}, {end:?})"))
+        .collect();
+    format!("[{}]", pairs.join(", "))
+}
@@ -0,0 +1,251 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use crate::cli::Cli;
+use crate::config::Config;
+use crate::consts;
+use crate::display;
+use crate::report::Report;
+use crate::types::FileData;
+use clap::{error, CommandFactory};
+use num_format::{SystemLocale, ToFormattedString};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+const DELTA_WIDTH: usize = 8;
+
+// Only line counts are saved so the lines added and removed for a file are
+// the increase or decrease in its lines; for a language they're the sums
+// for its files.
+#[derive(Clone, Copy, Debug, Default)]
+struct LineChange {
+    old: usize,
+    new: usize,
+    added: usize,
+    removed: usize,
+}
+
+impl LineChange {
+    fn new(old: usize, new: usize) -> Self {
+        Self {
+            old,
+            new,
+            added: new.saturating_sub(old),
+            removed: old.saturating_sub(new),
+        }
+    }
+
+    fn add(&mut self, other: &LineChange) {
+        self.old += other.old;
+        self.new += other.new;
+        self.added += other.added;
+        self.removed += other.removed;
+    }
+
+    fn delta(&self) -> String {
+        if self.old == 0 {
+            if self.new == 0 { "0.0%" } else { "new" }.to_string()
+        } else if self.new == 0 {
+            "gone".to_string()
+        } else {
+            let percent = (self.new as f64 - self.old as f64) * 100.0
+                / self.old as f64;
+            format!("{percent:+.1}%")
+        }
+    }
+}
+
+pub fn read_baseline(filename: &Path) -> Report {
+    fs::read_to_string(filename)
+        .map_err(|err| err.to_string())
+        .and_then(|text| {
+            serde_json::from_str(&text).map_err(|err| err.to_string())
+        })
+        .unwrap_or_else(|err| {
+            let mut cmd = Cli::command();
+            cmd.error(
+                error::ErrorKind::Io,
+                format!(
+                    "failed to read baseline {}: {err}",
+                    filename.display()
+                ),
+            )
+            .exit()
+        })
+}
+
+pub fn display(file_data: Vec<FileData>, config: Config, baseline: Report) {
+    let locale = SystemLocale::default().unwrap();
+    let file_changes = baseline.files.as_ref().map(|old_files| {
+        get_file_changes(old_files, &file_data, config.sortbylines)
+    });
+    let lang_changes =
+        get_lang_changes(&baseline, &file_data, file_changes.as_deref());
+    display_langs(&lang_changes, &locale, config.sortbylines);
+    if let Some(file_changes) = file_changes {
+        display_files(&file_changes, &locale);
+    } else {
+        println!(
+            "(the baseline has no per-file results since it was made \
+             with -S)"
+        );
+    }
+}
+
+// Returns the changes for every file that changed, appeared, or
+// disappeared, keyed by lang and filename
+fn get_file_changes(
+    old_files: &[FileData],
+    new_files: &[FileData],
+    sortbylines: bool,
+) -> Vec<(String, String, LineChange)> {
+    let mut changes = BTreeMap::<(&str, &str), LineChange>::new();
+    for file_datum in old_files {
+        changes
+            .entry((&file_datum.filename, &file_datum.lang))
+            .or_default()
+            .old = file_datum.lines;
+    }
+    for file_datum in new_files {
+        changes
+            .entry((&file_datum.filename, &file_datum.lang))
+            .or_default()
+            .new = file_datum.lines;
+    }
+    let mut changes: Vec<(String, String, LineChange)> = changes
+        .into_iter()
+        .filter(|(_, change)| change.old != change.new)
+        .map(|((filename, lang), change)| {
+            (
+                lang.to_string(),
+                filename.to_string(),
+                LineChange::new(change.old, change.new),
+            )
+        })
+        .collect();
+    if sortbylines {
+        changes.sort_by_key(|(_, filename, change)| {
+            (change.added.max(change.removed), filename.clone())
+        });
+    }
+    changes
+}
+
+fn get_lang_changes(
+    baseline: &Report,
+    file_data: &[FileData],
+    file_changes: Option<&[(String, String, LineChange)]>,
+) -> BTreeMap<String, LineChange> {
+    let mut changes = BTreeMap::<String, LineChange>::new();
+    if let Some(file_changes) = file_changes {
+        for (lang, _, change) in file_changes {
+            changes.entry(lang.clone()).or_default().add(change);
+        }
+    }
+    let mut new_lines = HashMap::<&str, usize>::new();
+    for file_datum in file_data {
+        *new_lines.entry(&file_datum.lang).or_default() += file_datum.lines;
+    }
+    for lang_total in &baseline.languages {
+        let new = new_lines.remove(lang_total.lang.as_str()).unwrap_or(0);
+        set_totals(&mut changes, &lang_total.lang, lang_total.lines, new);
+    }
+    for (lang, new) in new_lines {
+        set_totals(&mut changes, lang, 0, new);
+    }
+    changes
+}
+
+// Without per-file results the lines added and removed are the language's
+// net change
+fn set_totals(
+    changes: &mut BTreeMap<String, LineChange>,
+    lang: &str,
+    old: usize,
+    new: usize,
+) {
+    let change = changes
+        .entry(lang.to_string())
+        .or_insert_with(|| LineChange::new(old, new));
+    change.old = old;
+    change.new = new;
+}
+
+fn display_langs(
+    changes: &BTreeMap<String, LineChange>,
+    locale: &SystemLocale,
+    sortbylines: bool,
+) {
+    let mut total = LineChange::default();
+    let mut rows: Vec<(&str, &LineChange)> = vec![];
+    for (lang, change) in changes {
+        total.add(change);
+        if let Some(name) = display::row_name(lang) {
+            rows.push((name, change));
+        }
+    }
+    if sortbylines {
+        rows.sort_by_key(|(name, change)| {
+            (change.new, name.to_lowercase())
+        });
+    } else {
+        rows.sort_by_key(|(name, _)| name.to_lowercase());
+    }
+    let name_width =
+        display::name_width(rows.iter().map(|(name, _)| *name));
+    println!(
+        "{:name_width$}{}",
+        "",
+        format_titles("old lines", "new lines")
+    );
+    for (name, change) in &rows {
+        println!("{name:name_width$}{}", format_change(change, locale));
+    }
+    let row_width = name_width + get_columns_width();
+    display::rule(row_width);
+    println!("{:name_width$}{}", "Total", format_change(&total, locale));
+}
+
+fn display_files(
+    changes: &[(String, String, LineChange)],
+    locale: &SystemLocale,
+) {
+    println!();
+    if changes.is_empty() {
+        println!("no files changed");
+        return;
+    }
+    println!("{} file", format_titles("old", "new"));
+    for (_, filename, change) in changes {
+        println!("{} {filename}", format_change(change, locale));
+    }
+}
+
+fn format_titles(old: &str, new: &str) -> String {
+    let lines_width = consts::LINE_COUNT_WIDTH;
+    format!(
+        " {old:>lines_width$} {new:>lines_width$} {:>lines_width$} \
+        {:>lines_width$} {:>DELTA_WIDTH$}",
+        "added", "removed", "change"
+    )
+}
+
+fn format_change(change: &LineChange, locale: &SystemLocale) -> String {
+    let lines_width = consts::LINE_COUNT_WIDTH;
+    format!(
+        " {:>lines_width$} {:>lines_width$} {:>lines_width$} \
+        {:>lines_width$} {:>DELTA_WIDTH$}",
+        change.old.to_formatted_string(locale),
+        change.new.to_formatted_string(locale),
+        format!("+{}", change.added.to_formatted_string(locale)),
+        format!("-{}", change.removed.to_formatted_string(locale)),
+        change.delta()
+    )
+}
+
+fn get_columns_width() -> usize {
+    4 * (consts::LINE_COUNT_WIDTH + 1) + 1 + DELTA_WIDTH
+}
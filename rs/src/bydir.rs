@@ -0,0 +1,198 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use crate::config::Config;
+use crate::consts;
+use crate::display;
+use crate::report;
+use crate::types::{FileData, LineCounts};
+use crate::util;
+use num_format::{SystemLocale, ToFormattedString};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Component, PathBuf},
+};
+
+#[cfg(windows)]
+const BRANCH: &str = "+-- ";
+#[cfg(unix)]
+const BRANCH: &str = "├── ";
+#[cfg(windows)]
+const LAST_BRANCH: &str = "`-- ";
+#[cfg(unix)]
+const LAST_BRANCH: &str = "└── ";
+#[cfg(windows)]
+const STEM: &str = "|   ";
+#[cfg(unix)]
+const STEM: &str = "│   ";
+const SPACE: &str = "    ";
+
+// A folder's totals include those of all its subfolders
+#[derive(Default)]
+struct Node {
+    files: usize,
+    counts: LineCounts,
+    langs: HashMap<String, (usize, LineCounts)>,
+    children: BTreeMap<String, Node>,
+}
+
+impl Node {
+    fn add(&mut self, parts: &[String], file_datum: &FileData) {
+        let counts = file_datum.counts();
+        self.files += 1;
+        self.counts.add(&counts);
+        let lang = self.langs.entry(file_datum.lang.clone()).or_default();
+        lang.0 += 1;
+        lang.1.add(&counts);
+        if let Some((name, rest)) = parts.split_first() {
+            self.children
+                .entry(name.clone())
+                .or_default()
+                .add(rest, file_datum);
+        }
+    }
+}
+
+struct Row {
+    label: String,
+    files: usize,
+    counts: LineCounts,
+}
+
+pub fn display(file_data: Vec<FileData>, config: Config, depth: usize) {
+    if file_data.is_empty() {
+        return;
+    }
+    let trees = get_trees(&file_data, &config, depth);
+    let mut rows = vec![];
+    for (name, node) in &trees {
+        add_rows(&mut rows, node, name, "", "", config.sortbylines);
+    }
+    let label_width = rows
+        .iter()
+        .map(|row| row.label.chars().count())
+        .max()
+        .unwrap_or_default()
+        .max(consts::MIN_FILENAME_WIDTH)
+        .min(config.maxwidth);
+    let third = (label_width / 3) - 1;
+    let prefix_width = label_width + consts::FILE_COUNT_WIDTH + 7;
+    let count_width = consts::FILE_COUNT_WIDTH;
+    let locale = SystemLocale::default().unwrap();
+    display::display_column_titles(prefix_width);
+    println!();
+    for row in rows {
+        let label = util::elide(&row.label, third, label_width);
+        let s = if row.files == 1 { ' ' } else { 's' };
+        let count = row.files.to_formatted_string(&locale);
+        let columns = display::format_columns(&row.counts, &locale);
+        println!(
+            "{label:label_width$} {count:>count_width$} file{s}{columns}"
+        );
+    }
+    let row_width = prefix_width + display::get_columns_width();
+    display::rule(row_width);
+    display::display_total(&report::get_total(&file_data), row_width);
+    println!();
+}
+
+// Returns a tree for each folder searched, named as given, and one for the
+// folder of each file given explicitly, in the order they were given
+fn get_trees(
+    file_data: &[FileData],
+    config: &Config,
+    depth: usize,
+) -> Vec<(String, Node)> {
+    let roots: Vec<PathBuf> =
+        config.files.iter().map(|name| util::abspath(name)).collect();
+    let mut trees: Vec<(String, Node)> = vec![];
+    let mut index_for_name = HashMap::new();
+    let mut file_data: Vec<&FileData> = file_data.iter().collect();
+    file_data.sort_by_key(|file_datum| file_datum.root);
+    for file_datum in file_data {
+        let filename = file_datum.path.as_path();
+        let folder = filename.parent().unwrap_or(filename);
+        let root = &roots[file_datum.root];
+        let (name, parts) = if root.is_dir() {
+            (
+                config.files[file_datum.root].clone(),
+                folder
+                    .strip_prefix(root)
+                    .unwrap_or(folder)
+                    .components()
+                    .filter_map(|c| match c {
+                        Component::Normal(s) => {
+                            Some(format!("{}/", s.to_string_lossy()))
+                        }
+                        _ => None,
+                    })
+                    .take(depth)
+                    .collect(),
+            )
+        } else {
+            (folder.to_string_lossy().to_string(), vec![])
+        };
+        let index =
+            *index_for_name.entry(name.clone()).or_insert_with(|| {
+                trees.push((name, Node::default()));
+                trees.len() - 1
+            });
+        trees[index].1.add(&parts, file_datum);
+    }
+    trees
+}
+
+// Each folder's row is followed by a row for each of its languages and
+// then by its subfolders
+fn add_rows(
+    rows: &mut Vec<Row>,
+    node: &Node,
+    name: &str,
+    prefix: &str,
+    child_prefix: &str,
+    sortbylines: bool,
+) {
+    rows.push(Row {
+        label: format!("{prefix}{name}"),
+        files: node.files,
+        counts: node.counts,
+    });
+    let lang_prefix = format!(
+        "{child_prefix}{}",
+        if node.children.is_empty() { SPACE } else { STEM }
+    );
+    let mut langs: Vec<(&String, &(usize, LineCounts))> =
+        node.langs.iter().collect();
+    if sortbylines {
+        langs.sort_by_key(|(lang, (_, counts))| (counts.lines(), *lang));
+    } else {
+        langs.sort_by_key(|(lang, _)| lang.to_lowercase());
+    }
+    for (lang, (files, counts)) in langs {
+        if let Some(name) = display::row_name(lang) {
+            rows.push(Row {
+                label: format!("{lang_prefix}{name}"),
+                files: *files,
+                counts: *counts,
+            });
+        }
+    }
+    let mut children: Vec<(&String, &Node)> =
+        node.children.iter().collect();
+    if sortbylines {
+        children.sort_by_key(|(name, child)| (child.counts.lines(), *name));
+    }
+    let last = children.len().saturating_sub(1);
+    for (i, (name, child)) in children.into_iter().enumerate() {
+        let (branch, stem) =
+            if i == last { (LAST_BRANCH, SPACE) } else { (BRANCH, STEM) };
+        add_rows(
+            rows,
+            child,
+            name,
+            &format!("{child_prefix}{branch}"),
+            &format!("{child_prefix}{stem}"),
+            sortbylines,
+        );
+    }
+}
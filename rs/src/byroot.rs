@@ -0,0 +1,87 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use crate::config::Config;
+use crate::consts;
+use crate::display;
+use crate::report;
+use crate::types::FileData;
+use num_format::{SystemLocale, ToFormattedString};
+use std::collections::HashMap;
+
+// Lines per language (rows) for each file or folder given (columns) plus a
+// combined column
+pub fn display(file_data: Vec<FileData>, config: Config) {
+    if file_data.is_empty() {
+        return;
+    }
+    let roots = config.files.len();
+    let mut lines_for_lang = HashMap::<&str, Vec<usize>>::new();
+    let mut totals = vec![0; roots + 1];
+    let mut files = vec![0; roots + 1];
+    for file_datum in &file_data {
+        let lines = lines_for_lang
+            .entry(file_datum.lang.as_str())
+            .or_insert_with(|| vec![0; roots + 1]);
+        lines[file_datum.root] += file_datum.lines;
+        lines[roots] += file_datum.lines;
+        totals[file_datum.root] += file_datum.lines;
+        totals[roots] += file_datum.lines;
+        files[file_datum.root] += 1;
+        files[roots] += 1;
+    }
+    let mut rows: Vec<(&str, Vec<usize>)> = lines_for_lang
+        .into_iter()
+        .filter_map(|(lang, lines)| {
+            display::row_name(lang).map(|name| (name, lines))
+        })
+        .collect();
+    if config.sortbylines {
+        rows.sort_by_key(|(name, lines)| {
+            (lines[roots], name.to_lowercase())
+        });
+    } else {
+        rows.sort_by_key(|(name, _)| name.to_lowercase());
+    }
+    let name_width =
+        display::name_width(rows.iter().map(|(name, _)| *name));
+    let mut titles: Vec<&str> =
+        config.files.iter().map(|name| name.as_str()).collect();
+    titles.push("combined");
+    let widths: Vec<usize> = titles
+        .iter()
+        .map(|title| title.chars().count().max(consts::LINE_COUNT_WIDTH))
+        .collect();
+    let locale = SystemLocale::default().unwrap();
+    let row = |name: &str, values: &[String]| {
+        let mut line = format!("{name:name_width$}");
+        for (value, width) in values.iter().zip(&widths) {
+            line.push_str(&format!(" {value:>width$}"));
+        }
+        line
+    };
+    let format_counts = |counts: &[usize]| -> Vec<String> {
+        counts
+            .iter()
+            .map(|count| count.to_formatted_string(&locale))
+            .collect()
+    };
+    let titles: Vec<String> =
+        titles.iter().map(|title| title.to_string()).collect();
+    let header = row("lines", &titles);
+    println!("{header}");
+    for (name, lines) in &rows {
+        println!("{}", row(name, &format_counts(lines)));
+    }
+    let row_width = header.chars().count();
+    display::rule(row_width);
+    println!("{}", row("Total", &format_counts(&totals)));
+    println!("{}", row("Files", &format_counts(&files)));
+    let percents: Vec<String> = totals
+        .iter()
+        .map(|lines| {
+            format!("{:.1}%", report::percent(*lines, totals[roots]))
+        })
+        .collect();
+    println!("{}", row("%", &percents));
+}
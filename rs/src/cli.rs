@@ -1,8 +1,9 @@
 // Copyright © 2022 Mark Summerfield. All rights reserved.
 // License: GPLv3
 
-use clap::Parser;
-use std::ops::Range;
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+use std::{ops::Range, path::PathBuf};
 
 const MAXSIZE_RANGE: Range<usize> = 20..32767;
 
@@ -10,47 +11,221 @@ const MAXSIZE_RANGE: Range<usize> = 20..32767;
 #[clap(name = "clc", version)]
 pub struct Cli {
     /// Languages to count [default: all known]
-    #[arg(short, long, num_args(0..))]
+    #[arg(short, long, num_args(0..), global = true)]
     pub language: Option<Vec<String>>,
 
     /// Languages not to count [default: none].
     /// For example "-L d cpp" with no "-l" means count all languages except
     /// D and C++.
-    #[arg(short = 'L', long, num_args(0..))]
+    #[arg(short = 'L', long, num_args(0..), global = true)]
     pub skiplanguage: Option<Vec<String>>,
 
     /// Files and folders to exclude [default: .hidden and other sensible
-    /// ones]
-    #[arg(short, long, num_args(0..))]
+    /// ones]. Plain names match any path component; glob patterns (e.g.,
+    /// "**/generated/*.rs" or "*_pb2.py") match paths relative to the
+    /// folder searched
+    #[arg(short, long, num_args(0..), global = true)]
     pub exclude: Option<Vec<String>>,
 
-    /// Files to include (e.g., those without suffixes)
-    #[arg(short, long, num_args(0..))]
+    /// Files to include (e.g., those without suffixes). Plain names match
+    /// file names; glob patterns match paths relative to the folder
+    /// searched
+    #[arg(short, long, num_args(0..), global = true)]
     pub include: Option<Vec<String>>,
 
+    /// Regexes for files and folders to exclude, matched against paths
+    /// relative to the folder searched
+    #[arg(long, num_args(0..), global = true)]
+    pub exclude_regex: Vec<String>,
+
+    /// Regexes for files to include, matched against paths relative to the
+    /// folder searched
+    #[arg(long, num_args(0..), global = true)]
+    pub include_regex: Vec<String>,
+
+    /// Don't skip files and folders matched by .gitignore, .ignore,
+    /// .clcignore, .git/info/exclude, or git's core.excludesFile
+    #[arg(long, global = true)]
+    pub no_ignore: bool,
+
+    /// Exit with a nonzero status if any files couldn't be read [the
+    /// default is to report them and still exit with 0]
+    #[arg(long, global = true)]
+    pub strict: bool,
+
+    /// Config file (clc.toml, or clc.dat if it ends with .dat) to read
+    /// after those found automatically; may be repeated
+    #[arg(long, value_name = "FILE", num_args(1), global = true)]
+    pub config: Vec<String>,
+
+    /// Don't look for config files or use CLC_CONFIG (but still read any
+    /// --config files)
+    #[arg(long, global = true)]
+    pub no_config: bool,
+
+    /// List the supported languages (including any from config files)
+    /// with their extensions and file names and where they were defined,
+    /// then exit
+    #[arg(long)]
+    pub list_languages: bool,
+
+    /// Show the config files that were read, the effective excludes,
+    /// includes, and other settings, and the languages, then exit
+    #[arg(long)]
+    pub show_config: bool,
+
+    /// Explain why each given file or folder is or isn't counted (or
+    /// searched): every exclude, include, hidden, ignore file, language,
+    /// and shebang rule that applies, and the decision, then exit
+    #[arg(long, value_name = "PATH", num_args(1..))]
+    pub explain: Vec<String>,
+
+    /// Instead of counting, list the N [default: 20] most common
+    /// extensions (and names of files without one) that no language
+    /// recognizes, with how many files and lines have each
+    #[arg(
+        long,
+        value_name = "N",
+        num_args(0..=1),
+        default_missing_value = "20"
+    )]
+    pub unknown: Option<usize>,
+
+    /// Output a tree of folders showing the totals for each folder
+    /// (including its subfolders) broken down by language, instead of
+    /// per-file or summary totals (table format only)
+    #[arg(long)]
+    pub by_dir: bool,
+
+    /// Output each language's lines for each file or folder given side by
+    /// side, followed by their combined lines, instead of per-file or
+    /// summary totals (table format only)
+    #[arg(long, conflicts_with = "by_dir")]
+    pub by_root: bool,
+
+    /// Count the files as they were at the given git revision (e.g., a
+    /// tag, branch, or commit) by reading them from the repository
+    /// containing the first file or folder given, without checking out
+    #[arg(long, value_name = "REV", conflicts_with = "unknown")]
+    pub git_rev: Option<String>,
+
+    /// A JSON report from an earlier run (made with -f json) to compare
+    /// with: outputs the lines added and removed and the change for each
+    /// language and for each file that changed, appeared, or disappeared
+    /// (table format only)
+    #[arg(
+        long,
+        value_name = "REPORT",
+        conflicts_with_all = ["by_dir", "by_root"]
+    )]
+    pub baseline: Option<PathBuf>,
+
+    /// How many levels of folders below each folder searched to show with
+    /// --by-dir; files in deeper folders are counted in their ancestor at
+    /// this depth [default: 2]
+    #[arg(long, value_name = "N", requires = "by_dir")]
+    pub depth: Option<usize>,
+
+    /// Report paths relative to the folder searched that they were found
+    /// in (prefixed by that folder as given if there's more than one)
+    /// [default for json, csv, and tsv formats]
+    #[arg(long, global = true)]
+    pub relative: bool,
+
+    /// Report absolute paths [default for table, xml, and yaml formats]
+    #[arg(long, conflicts_with = "relative", global = true)]
+    pub absolute: bool,
+
     /// Maximum line width to use (e.g., for redirected output) [default:
     /// terminal width or needed width if less]
-    #[arg(short, long, value_parser=maxsize_in_range)]
+    #[arg(short, long, value_parser=maxsize_in_range, global = true)]
     pub maxwidth: Option<usize>,
 
-    /// Sort by lines [the default is to sort by names]
-    #[arg(short, long)]
+    /// Sort by lines [the default is to sort by names, or as set in
+    /// clc.toml]
+    #[arg(short, long, global = true)]
     pub sortbylines: bool,
 
+    /// Sort order, taking precedence over clc.toml's sort [default: names,
+    /// or as set in clc.toml]
+    #[arg(long, value_enum, conflicts_with = "sortbylines", global = true)]
+    pub sort: Option<Sort>,
+
     /// Summary: output per-language totals and total time if > 0.1 sec
     /// [the default is to output per-language and per-file totals]
-    #[arg(short = 'S', long)]
+    #[arg(short = 'S', long, global = true)]
     pub summary: bool,
 
+    /// Output per-language and per-file totals even if clc.toml sets
+    /// summary = true
+    #[arg(long, conflicts_with = "summary", global = true)]
+    pub no_summary: bool,
+
+    /// Output format [default: table, or as set in clc.toml]
+    #[arg(short, long, value_enum)]
+    pub format: Option<Format>,
+
     /// Files to count or the folders to recursively search [default: .]
+    /// (use ./diff for a folder called diff)
     pub file: Option<Vec<String>>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Output the code lines added, removed, and modified between two git
+    /// revisions for each language and each file, using the same
+    /// languages, excludes, includes, and ignore files as when counting
+    /// (table format only). Blank and comment lines aren't counted. Within
+    /// each changed block of lines, as many code lines as were both
+    /// removed and added count as modified.
+    Diff {
+        /// The older revision (e.g., a tag, branch, or commit)
+        rev1: String,
+
+        /// The newer revision
+        rev2: String,
+
+        /// Files and folders to compare [default: .]
+        file: Option<Vec<String>>,
+    },
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    /// Case-insensitive alphabetically by name
+    Names,
+    /// By number of lines
+    Lines,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Human-readable table
+    Table,
+    /// Per-file records, per-language subtotals, and grand totals as JSON
+    Json,
+    /// Comma-separated values with a header row and one row per file (or
+    /// per language if summarizing)
+    Csv,
+    /// Tab-separated values with a header row and one row per file (or
+    /// per language if summarizing)
+    Tsv,
+    /// cloc-compatible XML
+    Xml,
+    /// cloc-compatible YAML
+    Yaml,
 }
 
 fn maxsize_in_range(s: &str) -> Result<usize, String> {
     let maxsize: usize =
         s.parse().map_err(|_| format!("invalid maxsize: {:?}", s))?;
     if MAXSIZE_RANGE.contains(&maxsize) {
-        Ok(maxsize as usize)
+        Ok(maxsize)
     } else {
         Err(format!(
             "maxsize must be in range {}-{}",
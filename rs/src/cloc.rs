@@ -0,0 +1,137 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+// Output that mirrors cloc's --xml and --yaml report schemas so that tools
+// which consume cloc's reports can consume clc's instead. If not
+// summarizing, the per-file results are output as well as the per-language
+// ones (like cloc's --by-file-by-lang). Each language also has its
+// percentage of the total lines, which cloc doesn't provide.
+
+use crate::config::Config;
+use crate::report::Report;
+use crate::types::FileData;
+use std::time::Instant;
+
+const URL: &str = "github.com/mark-summerfield/clc";
+
+pub fn display_xml(file_data: Vec<FileData>, config: Config, t: Instant) {
+    let report = Report::new(file_data, &config);
+    println!("<?xml version=\"1.0\" encoding=\"UTF-8\"?><results>");
+    println!("<header>");
+    for (key, value) in header(&report, t) {
+        println!("  <{key}>{value}</{key}>");
+    }
+    println!("</header>");
+    if let Some(files) = &report.files {
+        println!("<files>");
+        for file_datum in files {
+            println!(
+                "  <file name=\"{}\" blank=\"{}\" comment=\"{}\" \
+                code=\"{}\" language=\"{}\" />",
+                xml_escape(&file_datum.filename),
+                file_datum.blanks,
+                file_datum.comments,
+                file_datum.code,
+                xml_escape(report.name_for_lang(&file_datum.lang))
+            );
+        }
+        let total = &report.total;
+        println!(
+            "  <total blank=\"{}\" comment=\"{}\" code=\"{}\" />",
+            total.blanks, total.comments, total.code
+        );
+        println!("</files>");
+    }
+    println!("<languages>");
+    for lang_total in &report.languages {
+        println!(
+            "  <language name=\"{}\" files_count=\"{}\" blank=\"{}\" \
+            comment=\"{}\" code=\"{}\" percent=\"{}\" />",
+            xml_escape(&lang_total.name),
+            lang_total.files,
+            lang_total.blanks,
+            lang_total.comments,
+            lang_total.code,
+            lang_total.percent
+        );
+    }
+    let total = &report.total;
+    println!(
+        "  <total sum_files=\"{}\" blank=\"{}\" comment=\"{}\" code=\"{}\" />",
+        total.files, total.blanks, total.comments, total.code
+    );
+    println!("</languages>");
+    println!("</results>");
+}
+
+pub fn display_yaml(file_data: Vec<FileData>, config: Config, t: Instant) {
+    let report = Report::new(file_data, &config);
+    println!("---\n# {URL}");
+    println!("header :");
+    for (key, value) in header(&report, t) {
+        println!("  {key:<18} : {value}");
+    }
+    if let Some(files) = &report.files {
+        for file_datum in files {
+            println!("{} :", yaml_key(&file_datum.filename));
+            println!("  blank: {}", file_datum.blanks);
+            println!("  comment: {}", file_datum.comments);
+            println!("  code: {}", file_datum.code);
+            println!(
+                "  language: {}",
+                yaml_key(report.name_for_lang(&file_datum.lang))
+            );
+        }
+    }
+    for lang_total in &report.languages {
+        println!("{} :", yaml_key(&lang_total.name));
+        println!("  nFiles: {}", lang_total.files);
+        println!("  blank: {}", lang_total.blanks);
+        println!("  comment: {}", lang_total.comments);
+        println!("  code: {}", lang_total.code);
+        println!("  percent: {}", lang_total.percent);
+    }
+    let total = &report.total;
+    println!("SUM:");
+    println!("  blank: {}", total.blanks);
+    println!("  comment: {}", total.comments);
+    println!("  code: {}", total.code);
+    println!("  nFiles: {}", total.files);
+}
+
+fn header(report: &Report, t: Instant) -> Vec<(&'static str, String)> {
+    let secs = t.elapsed().as_secs_f64().max(f64::EPSILON);
+    let total = &report.total;
+    vec![
+        ("cloc_url", URL.to_string()),
+        ("cloc_version", env!("CARGO_PKG_VERSION").to_string()),
+        ("elapsed_seconds", format!("{secs:.6}")),
+        ("n_files", total.files.to_string()),
+        ("n_lines", total.lines.to_string()),
+        ("files_per_second", format!("{:.1}", total.files as f64 / secs)),
+        ("lines_per_second", format!("{:.1}", total.lines as f64 / secs)),
+    ]
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// Keys are output plain (as cloc does) unless YAML would misread them
+fn yaml_key(s: &str) -> String {
+    let is_plain = !s.is_empty()
+        && !s.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@`".contains(c))
+        && !s.contains(": ")
+        && !s.contains(" #")
+        && !s.ends_with([':', ' '])
+        && !s.contains(|c: char| c.is_control());
+    if is_plain {
+        s.to_string()
+    } else {
+        format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
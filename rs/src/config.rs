@@ -1,50 +1,88 @@
 // Copyright © 2022 Mark Summerfield. All rights reserved.
 // License: GPLv3
 
-use crate::cli::Cli;
+use crate::cli::{Cli, Command, Format, Sort};
+use crate::configfile;
 use crate::consts;
-use crate::types::LangData;
-use anyhow::Result;
+use crate::util;
 use clap::{error, CommandFactory, FromArgMatches};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::RegexSet;
 use std::{
-    collections::{HashMap, HashSet},
-    env,
-    fs::File,
-    io::prelude::*,
-    path::Path,
+    collections::HashSet,
+    path::{Component, Path, PathBuf},
 };
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Config {
     pub langs: HashSet<String>,
     pub exclude: HashSet<String>,
     pub include: HashSet<String>,
+    pub exclude_patterns: PathPatterns,
+    pub include_patterns: PathPatterns,
     pub maxwidth: usize,
     pub sortbylines: bool,
     pub summary: bool,
-    pub files: HashSet<String>,
+    pub by_dir: Option<usize>, // The depth
+    pub by_root: bool,
+    pub baseline: Option<PathBuf>,
+    pub git_rev: Option<String>,
+    pub diff: Option<(String, String)>, // The old and new revisions
+    pub format: Format,
+    pub relative: bool,
+    pub no_ignore: bool,
+    pub strict: bool,
+    pub config_files: Vec<PathBuf>,
+    pub list_languages: bool,
+    pub show_config: bool,
+    pub explain: Vec<String>,
+    pub unknown: Option<usize>,
+    pub files: Vec<String>, // In the order given, without duplicates
 }
 
 impl Config {
     pub fn new() -> Self {
-        read_config_files(); // NOTE must come first
         let command = Cli::command();
         let cli = Cli::from_arg_matches(
             &command.about(get_about()).get_matches(),
         )
         .unwrap();
+        if cli.command.is_some() {
+            check_diff_conflicts(&cli);
+        }
+        // NOTE must come before anything that uses consts::DATA_FOR_LANG
+        let (defaults, config_files) =
+            configfile::read_config_files(&cli.config, cli.no_config)
+                .unwrap_or_else(|err| {
+                    let mut cmd = Cli::command();
+                    cmd.error(error::ErrorKind::Io, err).exit()
+                });
         let langs = get_langs(cli.language, cli.skiplanguage);
         let mut exclude = HashSet::from_iter(
             consts::EXCLUDE.get().iter().map(|s| s.to_string()),
         );
-        if let Some(excl) = cli.exclude {
-            exclude.extend(excl);
-        }
-        let include = if let Some(incl) = cli.include {
-            HashSet::from_iter(incl)
-        } else {
-            HashSet::new()
-        };
+        // The command line's patterns are added to those from clc.toml
+        let (excl, exclude_globs) = split_patterns(
+            defaults
+                .exclude
+                .into_iter()
+                .chain(cli.exclude.unwrap_or_default()),
+        );
+        exclude.extend(excl);
+        let (include, include_globs) = split_patterns(
+            defaults
+                .include
+                .into_iter()
+                .chain(cli.include.unwrap_or_default()),
+        );
+        let mut exclude_regex = defaults.exclude_regex;
+        exclude_regex.extend(cli.exclude_regex);
+        let mut include_regex = defaults.include_regex;
+        include_regex.extend(cli.include_regex);
+        let exclude_patterns =
+            PathPatterns::new(&exclude_globs, &exclude_regex);
+        let include_patterns =
+            PathPatterns::new(&include_globs, &include_regex);
         let maxwidth = if let Some(maxwidth) = cli.maxwidth {
             maxwidth // Always in range 20..32767
         } else if let Some((width, _)) = term_size::dimensions() {
@@ -53,24 +91,209 @@ impl Config {
             80
         };
         // Internally we use this purely to elide filenames, hence the -
-        let maxwidth = maxwidth - (consts::LINE_COUNT_WIDTH + 2);
-        let files = if let Some(file) = cli.file {
-            HashSet::from_iter(file)
+        let maxwidth = maxwidth
+            .saturating_sub(
+                consts::LINE_COUNT_WIDTH
+                    + 3 * (consts::COUNT_WIDTH + 1)
+                    + 2,
+            )
+            .max(consts::MIN_FILENAME_WIDTH);
+        let format =
+            cli.format.or(defaults.format).unwrap_or(Format::Table);
+        let (diff, file) = match cli.command {
+            Some(Command::Diff { rev1, rev2, file }) => {
+                (Some((rev1, rev2)), file.or(cli.file))
+            }
+            None => (None, cli.file),
+        };
+        let table_only = [
+            (cli.by_dir, "--by-dir"),
+            (cli.by_root, "--by-root"),
+            (cli.baseline.is_some(), "--baseline"),
+            (diff.is_some(), "diff"),
+        ];
+        if format != Format::Table {
+            if let Some((_, option)) =
+                table_only.iter().find(|(used, _)| *used)
+            {
+                let mut cmd = Cli::command();
+                cmd.error(
+                    error::ErrorKind::ArgumentConflict,
+                    format!(
+                        "{option} can only be used with the table format"
+                    ),
+                )
+                .exit();
+            }
+        }
+        let files = if let Some(file) = file {
+            unique_files(file)
         } else {
-            HashSet::from([".".to_string()])
+            vec![".".to_string()]
         };
         Self {
             langs,
             exclude,
             include,
+            exclude_patterns,
+            include_patterns,
             maxwidth,
-            sortbylines: cli.sortbylines,
-            summary: cli.summary,
+            // The command line's options take precedence over clc.toml's
+            sortbylines: cli.sortbylines
+                || cli.sort.or(defaults.sort) == Some(Sort::Lines),
+            summary: cli.summary
+                || (!cli.no_summary
+                    && defaults.summary.unwrap_or_default()),
+            by_dir: cli.by_dir.then(|| cli.depth.unwrap_or(2)),
+            by_root: cli.by_root,
+            format,
+            // Relative paths make machine-readable reports reproducible, and
+            // baselines are saved JSON reports
+            relative: cli.relative
+                || (!cli.absolute
+                    && (cli.baseline.is_some()
+                        || diff.is_some()
+                        || matches!(
+                            format,
+                            Format::Json | Format::Csv | Format::Tsv
+                        ))),
+            baseline: cli.baseline,
+            git_rev: cli.git_rev,
+            diff,
+            no_ignore: cli.no_ignore,
+            strict: cli.strict,
+            config_files,
+            list_languages: cli.list_languages,
+            show_config: cli.show_config,
+            explain: cli.explain,
+            unknown: cli.unknown,
             files,
         }
     }
 }
 
+// Glob patterns and regexes that are matched against relative paths
+#[derive(Clone, Debug)]
+pub struct PathPatterns {
+    pub globs: Vec<String>, // As given, for --show-config
+    globset: GlobSet,
+    regexes: RegexSet,
+}
+
+impl PathPatterns {
+    fn new(globs: &[String], regexes: &[String]) -> Self {
+        let mut builder = GlobSetBuilder::new();
+        for glob in globs {
+            // As with .gitignore, a pattern without a / matches at any
+            // depth and a leading / anchors a pattern to the root
+            let pattern = if let Some(glob) = glob.strip_prefix('/') {
+                glob.to_string()
+            } else if glob.contains('/') || glob.starts_with("**") {
+                glob.to_string()
+            } else {
+                format!("**/{glob}")
+            };
+            match Glob::new(&pattern) {
+                Ok(glob) => {
+                    builder.add(glob);
+                }
+                Err(err) => invalid_pattern(&err.to_string()),
+            }
+        }
+        let globset = builder
+            .build()
+            .unwrap_or_else(|err| invalid_pattern(&err.to_string()));
+        let regexes = RegexSet::new(regexes)
+            .unwrap_or_else(|err| invalid_pattern(&err.to_string()));
+        Self { globs: globs.to_vec(), globset, regexes }
+    }
+
+    pub fn regexes(&self) -> &[String] {
+        self.regexes.patterns()
+    }
+
+    // Returns the first glob (as given) or regex that matches, if any
+    pub fn matching(&self, relpath: &Path) -> Option<&str> {
+        if self.globset.is_empty() && self.regexes.is_empty() {
+            return None;
+        }
+        let relpath = slashed(relpath);
+        if let Some(&i) = self.globset.matches(&relpath).first() {
+            return Some(&self.globs[i]);
+        }
+        self.regexes
+            .matches(&relpath)
+            .iter()
+            .next()
+            .map(|i| self.regexes.patterns()[i].as_str())
+    }
+}
+
+// Use / separators on every platform so patterns are portable
+fn slashed(relpath: &Path) -> String {
+    relpath
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(s) => Some(s.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// The options that only apply when counting can be given before diff
+// (though not after it)
+fn check_diff_conflicts(cli: &Cli) {
+    let counting_only = [
+        (!cli.explain.is_empty(), "--explain"),
+        (cli.unknown.is_some(), "--unknown"),
+        (cli.by_dir, "--by-dir"),
+        (cli.by_root, "--by-root"),
+        (cli.git_rev.is_some(), "--git-rev"),
+        (cli.baseline.is_some(), "--baseline"),
+    ];
+    if let Some((_, option)) = counting_only.iter().find(|(used, _)| *used)
+    {
+        let mut cmd = Cli::command();
+        cmd.error(
+            error::ErrorKind::ArgumentConflict,
+            format!("{option} can't be used with diff"),
+        )
+        .exit();
+    }
+}
+
+fn invalid_pattern(message: &str) -> ! {
+    let mut cmd = Cli::command();
+    cmd.error(error::ErrorKind::ValueValidation, message).exit()
+}
+
+// Keeps the first of any names for the same file or folder, e.g., "." and
+// "./"
+fn unique_files(names: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    names
+        .into_iter()
+        .filter(|name| seen.insert(util::abspath(name)))
+        .collect()
+}
+
+// Returns the plain names (which are matched exactly) and the glob patterns
+fn split_patterns(
+    patterns: impl Iterator<Item = String>,
+) -> (HashSet<String>, Vec<String>) {
+    let mut names = HashSet::new();
+    let mut globs = vec![];
+    for pattern in patterns {
+        if pattern.contains(['*', '?', '[', '{', '/']) {
+            globs.push(pattern);
+        } else {
+            names.insert(pattern);
+        }
+    }
+    (names, globs)
+}
+
 fn get_langs(
     language: Option<Vec<String>>,
     skiplanguage: Option<Vec<String>>,
@@ -109,118 +332,91 @@ fn get_langs(
     langs
 }
 
-fn read_config_files() {
-    let mut data_for_lang = initial_data_for_lang();
-    // We ignore errors & only care about files we find
-    if let Ok(filename) = env::current_exe() {
-        if let Some(filename) = filename.parent() {
-            let filename = filename.join("clc.dat");
-            let _ = read_config_file(&filename, &mut data_for_lang);
-        }
-    }
-    if let Some(home) = dirs::home_dir() {
-        let home1 = home.join("clc.dat");
-        let _ = read_config_file(&home1, &mut data_for_lang);
-        let home2 = home.join(".config/clc.dat");
-        let _ = read_config_file(&home2, &mut data_for_lang);
-    }
-    if let Ok(filename) = env::current_dir() {
-        let filename = filename.join("clc.dat");
-        let _ = read_config_file(&filename, &mut data_for_lang);
-    }
-    consts::DATA_FOR_LANG.set(data_for_lang);
+fn get_about() -> String {
+    "Counts the code, comment, and blank lines in the code files for \
+the languages processed (excluding . folders).
+
+Use --list-languages to see the supported language names (e.g., for -l \
+and -L), --show-config to see the config files that were read and the \
+effective settings, and --explain to see why particular files are or \
+aren't counted.
+
+The clc.toml and clc.dat files are looked for in the clc executable's \
+folder, the home folder, the $XDG_CONFIG_HOME (or home/.config) folder and \
+its clc subfolder, and every folder from the root of the git repository \
+containing the current folder down to the current folder (or just the \
+current folder if it isn't in a repository), with clc.toml read after \
+clc.dat in each. Then the file named by the CLC_CONFIG environment \
+variable is read, and then any --config files; later files take \
+precedence. Use --no-config to only read --config files. clc.toml files \
+have the form:
+    [defaults]
+    exclude = [\"vendor\", \"**/generated/*.rs\"]
+    sort = \"lines\"    # or \"names\"
+    format = \"json\"   # or any other --format
+    [languages.pas]
+    name = \"Pascal\"
+    exts = [\"pas\", \"pp\"]
+    line_comments = [\"//\"]
+    block_comments = [[\"(*\", \"*)\"], [\"{\", \"}\"]]
+The defaults may also have include, exclude_regex, include_regex, and \
+summary settings, and languages may also have filenames, interpreters, \
+nested_comments, strings, raw_strings, multiline_strings, and \
+char_literals. Fields omitted for an existing language keep their \
+values. clc.dat files have the form: 
+    lang|Name|ext1 [ext2 ... [extN]][|line comments[|block comments\
+[|interpreters[|filenames]]]]
+For example:
+    pas|Pascal|pas pp inc|//|(* *)
+    sql|SQL|sql|--|/* */
+    sh|Shell|sh|#||sh bash dash ksh zsh|.bashrc .profile
+    just|Just|just|#|||justfile Justfile *.justfile
+Block comments are given as start end pairs. Interpreters are the program \
+names used in shebang (#!) lines, and are matched with or without a \
+version suffix (e.g., python matches python3.11). Filenames are whole file \
+names or globs (e.g., Dockerfile.*) \
+and take precedence over extensions. Without comment syntax all nonblank \
+lines count as code. Blank lines and lines beginning with `#` are ignored."
+        .to_string()
 }
 
-fn read_config_file(
-    filename: &Path,
-    data_for_lang: &mut HashMap<String, LangData>,
-) -> Result<()> {
-    let mut file = File::open(&filename)?;
-    let mut text = String::new();
-    file.read_to_string(&mut text)?;
-    for line in text.lines() {
-        let line = line.trim();
-        if line.is_empty() || line.starts_with('#') {
-            continue;
-        }
-        let parts: Vec<&str> = line.splitn(3, '|').collect();
-        if parts.len() == 3 {
-            let lang = parts[0].trim();
-            let name = parts[1].trim();
-            let mut exts: HashSet<&str> = HashSet::new();
-            for ext in parts[2].split_whitespace() {
-                if let Some(ext) = ext.strip_prefix('.') {
-                    exts.insert(ext);
-                } else {
-                    exts.insert(ext);
-                }
-            }
-            data_for_lang
-                .insert(lang.to_string(), LangData::new(name, exts));
-        } else {
-            eprintln!("ignoring invalid line from {filename:?}: {line}");
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matching(patterns: &PathPatterns, relpath: &str) -> Option<String> {
+        patterns.matching(Path::new(relpath)).map(|s| s.to_string())
     }
-    Ok(())
-}
 
-fn initial_data_for_lang() -> HashMap<String, LangData> {
-    HashMap::from([
-        ("c".to_string(), LangData::new("C", HashSet::from(["h", "c"]))),
-        (
-            "cpp".to_string(),
-            LangData::new(
-                "C++",
-                HashSet::from(["hpp", "hxx", "cpp", "cxx"]),
-            ),
-        ),
-        ("d".to_string(), LangData::new("D", HashSet::from(["d"]))),
-        ("go".to_string(), LangData::new("Go", HashSet::from(["go"]))),
-        (
-            "java".to_string(),
-            LangData::new("Java", HashSet::from(["java"])),
-        ),
-        ("jl".to_string(), LangData::new("Julia", HashSet::from(["jl"]))),
-        ("nim".to_string(), LangData::new("Nim", HashSet::from(["nim"]))),
-        (
-            "pl".to_string(),
-            LangData::new("Perl", HashSet::from(["pl", "PL", "pm"])),
-        ),
-        (
-            "py".to_string(),
-            LangData::new("Python", HashSet::from(["py", "pyw"])),
-        ),
-        ("rb".to_string(), LangData::new("Ruby", HashSet::from(["rb"]))),
-        ("rs".to_string(), LangData::new("Rust", HashSet::from(["rs"]))),
-        ("tcl".to_string(), LangData::new("Tcl", HashSet::from(["tcl"]))),
-        (
-            "vala".to_string(),
-            LangData::new("Vala", HashSet::from(["vala"])),
-        ),
-    ])
-}
+    #[test]
+    fn glob_patterns() {
+        let globs = ["*_pb2.py", "/top/*.rs", "**/gen/*.rs", "docs/*.md"]
+            .map(String::from);
+        let patterns = PathPatterns::new(&globs, &[]);
+        assert_eq!(
+            matching(&patterns, "a/b/x_pb2.py").unwrap(),
+            "*_pb2.py"
+        );
+        assert_eq!(matching(&patterns, "top/a.rs").unwrap(), "/top/*.rs");
+        assert_eq!(matching(&patterns, "sub/top/a.rs"), None);
+        assert_eq!(
+            matching(&patterns, "x/gen/a.rs").unwrap(),
+            "**/gen/*.rs"
+        );
+        assert_eq!(matching(&patterns, "docs/a.md").unwrap(), "docs/*.md");
+        assert_eq!(matching(&patterns, "src/docs/a.md"), None);
+    }
 
-fn get_about() -> String {
-    let mut langs = Vec::from_iter(
-        consts::DATA_FOR_LANG.get().keys().map(|s| s.to_string()),
-    );
-    langs.sort_unstable_by_key(|a| a.to_lowercase());
-    let langs = langs.join(" ");
-    format!(
-        "Counts the lines in the code files for the languages \
-processed (excluding . folders).
-
-Supported language names: {langs}
-
-The above names are the built-in ones and those from any clc.dat files \
-that were found.
-The clc.dat files are looked for in the clc executable's folder, the home \
-folder, the home/.config folder, and the current folder. These files have \
-the form: 
-    lang|Name|ext1 [ext2 [ext3 ... [extN]]]
-For example:
-    pas|Pascal|pas pp inc
-    sql|SQL|sql
-Blank lines and lines beginning with `#` are ignored."
-    )
+    #[test]
+    fn regex_patterns() {
+        let regexes = [r"^vendor/", r"_test\.go$"].map(String::from);
+        let patterns = PathPatterns::new(&[], &regexes);
+        assert_eq!(matching(&patterns, "vendor/a.go").unwrap(), "^vendor/");
+        assert_eq!(
+            matching(&patterns, "a/b_test.go").unwrap(),
+            r"_test\.go$"
+        );
+        assert_eq!(matching(&patterns, "src/vendor/a.go"), None);
+        assert_eq!(matching(&PathPatterns::new(&[], &[]), "a.go"), None);
+    }
 }
@@ -0,0 +1,459 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use crate::cli::{Format, Sort};
+use crate::consts;
+use crate::types::LangData;
+use anyhow::Result;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+const CONFIG_ENV: &str = "CLC_CONFIG";
+
+// Settings from clc.toml files that apply unless overridden on the command
+// line
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Defaults {
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude_regex: Vec<String>,
+    #[serde(default)]
+    pub include_regex: Vec<String>,
+    pub sort: Option<Sort>,
+    pub format: Option<Format>,
+    pub summary: Option<bool>,
+}
+
+impl Defaults {
+    // Lists accumulate; later files' settings replace earlier ones
+    fn merge(&mut self, other: Defaults) {
+        self.exclude.extend(other.exclude);
+        self.include.extend(other.include);
+        self.exclude_regex.extend(other.exclude_regex);
+        self.include_regex.extend(other.include_regex);
+        if other.sort.is_some() {
+            self.sort = other.sort;
+        }
+        if other.format.is_some() {
+            self.format = other.format;
+        }
+        if other.summary.is_some() {
+            self.summary = other.summary;
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    defaults: Defaults,
+    #[serde(default)]
+    languages: BTreeMap<String, LangSpec>,
+}
+
+// The same fields as languages.toml; any that are omitted keep their
+// built-in (or earlier) values, so only a new language needs a name
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LangSpec {
+    name: Option<String>,
+    exts: Option<Vec<String>>,
+    filenames: Option<Vec<String>>,
+    interpreters: Option<Vec<String>>,
+    line_comments: Option<Vec<String>>,
+    block_comments: Option<Vec<(String, String)>>,
+    nested_comments: Option<Vec<(String, String)>>,
+    strings: Option<Vec<(String, String)>>,
+    raw_strings: Option<Vec<(String, String)>>,
+    multiline_strings: Option<Vec<String>>,
+    char_literals: Option<bool>,
+}
+
+// Sets consts::DATA_FOR_LANG and returns the defaults. Files are read in
+// order of increasing precedence: those found automatically (unless
+// no_config), then CLC_CONFIG's (unless no_config), then those given
+// explicitly. In each folder clc.dat is read before clc.toml.
+pub fn read_config_files(
+    explicit: &[String],
+    no_config: bool,
+) -> Result<(Defaults, Vec<PathBuf>)> {
+    let mut data_for_lang = initial_data_for_lang();
+    let mut defaults = Defaults::default();
+    let mut read = vec![]; // The files that were found
+    let mut filenames = vec![];
+    if !no_config {
+        for dir in config_dirs() {
+            filenames.push(dir.join("clc.dat"));
+            filenames.push(dir.join("clc.toml"));
+        }
+    }
+    let mut seen = HashSet::new();
+    for filename in filenames {
+        // e.g., if the current folder is the home folder
+        let key = filename.canonicalize().unwrap_or(filename.clone());
+        if seen.insert(key) {
+            if let Some(text) = read_text(&filename) {
+                read_config_text(
+                    &filename,
+                    &text,
+                    &mut data_for_lang,
+                    &mut defaults,
+                );
+                read.push(filename);
+            }
+        }
+    }
+    let mut explicit: Vec<PathBuf> =
+        explicit.iter().map(PathBuf::from).collect();
+    if !no_config {
+        if let Some(filename) = env::var_os(CONFIG_ENV) {
+            if !filename.is_empty() {
+                explicit.insert(0, PathBuf::from(filename));
+            }
+        }
+    }
+    for filename in explicit {
+        // A config file that was asked for must exist
+        let text = fs::read_to_string(&filename).map_err(|err| {
+            anyhow::anyhow!(
+                "failed to read config file {}: {err}",
+                filename.display()
+            )
+        })?;
+        read_config_text(
+            &filename,
+            &text,
+            &mut data_for_lang,
+            &mut defaults,
+        );
+        read.push(filename);
+    }
+    consts::DATA_FOR_LANG.set(data_for_lang);
+    Ok((defaults, read))
+}
+
+// Files whose names end with .dat are clc.dat files; others are clc.toml
+fn read_config_text(
+    filename: &Path,
+    text: &str,
+    data_for_lang: &mut HashMap<String, LangData>,
+    defaults: &mut Defaults,
+) {
+    if filename.extension().is_some_and(|ext| ext == "dat") {
+        read_dat_text(filename, text, data_for_lang);
+    } else if let Some(file_defaults) =
+        read_toml_text(filename, text, data_for_lang)
+    {
+        defaults.merge(file_defaults);
+    }
+}
+
+// In order: the clc executable's folder, the home folder, the XDG config
+// folder (and its clc subfolder), and every folder from the root of the
+// repository (or project) containing the current folder down to the
+// current folder itself
+fn config_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if let Ok(filename) = env::current_exe() {
+        if let Some(dir) = filename.parent() {
+            dirs.push(dir.to_path_buf());
+        }
+    }
+    if let Some(home) = dirs::home_dir() {
+        dirs.push(home.clone());
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .unwrap_or_else(|| home.join(".config"));
+        dirs.push(config_home.clone());
+        dirs.push(config_home.join("clc"));
+    }
+    if let Ok(cwd) = env::current_dir() {
+        dirs.extend(project_dirs(&cwd));
+    }
+    dirs
+}
+
+// Returns the folders from the repository root down to cwd, or just cwd if
+// it isn't inside a repository
+fn project_dirs(cwd: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    for dir in cwd.ancestors() {
+        dirs.push(dir.to_path_buf());
+        if dir.join(".git").exists() {
+            dirs.reverse();
+            return dirs;
+        }
+    }
+    vec![cwd.to_path_buf()]
+}
+
+// Missing files are normal; any other failure to read is reported
+fn read_text(filename: &Path) -> Option<String> {
+    match fs::read_to_string(filename) {
+        Ok(text) => Some(text),
+        Err(err) if err.kind() == ErrorKind::NotFound => None,
+        Err(err) => {
+            eprintln!("failed to read {}: {err}", filename.display());
+            None
+        }
+    }
+}
+
+fn read_dat_text(
+    filename: &Path,
+    text: &str,
+    data_for_lang: &mut HashMap<String, LangData>,
+) {
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_dat_line(line) {
+            Ok((lang, spec)) => {
+                let origin = format!("{}:{}", filename.display(), i + 1);
+                add_lang(data_for_lang, lang, spec, origin);
+            }
+            Err(err) => eprintln!(
+                "{}:{}: ignoring invalid line: {err}",
+                filename.display(),
+                i + 1
+            ),
+        }
+    }
+}
+
+// Empty or omitted fields are left unset so that, as for clc.toml, an
+// existing language keeps their values
+fn parse_dat_line(line: &str) -> Result<(String, LangSpec)> {
+    let parts: Vec<&str> = line.splitn(7, '|').collect();
+    if parts.len() < 3 {
+        anyhow::bail!("expected at least lang|Name|exts");
+    }
+    let words = |i: usize| -> Option<Vec<String>> {
+        parts
+            .get(i)
+            .map(|part| {
+                part.split_whitespace().map(|s| s.to_string()).collect()
+            })
+            .filter(|words: &Vec<String>| !words.is_empty())
+    };
+    let block_comments = match words(4) {
+        Some(delims) if !delims.len().is_multiple_of(2) => {
+            anyhow::bail!("every block comment start needs an end")
+        }
+        Some(delims) => Some(
+            delims
+                .chunks(2)
+                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                .collect(),
+        ),
+        None => None,
+    };
+    Ok((
+        parts[0].trim().to_string(),
+        LangSpec {
+            name: Some(parts[1].trim().to_string()),
+            exts: Some(words(2).unwrap_or_default()),
+            filenames: words(6),
+            interpreters: words(5),
+            line_comments: words(3),
+            block_comments,
+            nested_comments: None,
+            strings: None,
+            raw_strings: None,
+            multiline_strings: None,
+            char_literals: None,
+        },
+    ))
+}
+
+fn read_toml_text(
+    filename: &Path,
+    text: &str,
+    data_for_lang: &mut HashMap<String, LangData>,
+) -> Option<Defaults> {
+    let config: ConfigFile = match toml::from_str(text) {
+        Ok(config) => config,
+        Err(err) => {
+            let line =
+                err.span().map_or(0, |span| line_for(text, span.start));
+            eprintln!(
+                "{}:{line}: ignoring invalid file: {}",
+                filename.display(),
+                err.message()
+            );
+            return None;
+        }
+    };
+    for (lang, spec) in config.languages {
+        let line = line_for_table(text, &lang);
+        let origin = format!("{}:{line}", filename.display());
+        if spec.name.is_none() && !data_for_lang.contains_key(&lang) {
+            eprintln!(
+                "{}:{line}: ignoring new language {lang} without a name",
+                filename.display()
+            );
+        } else {
+            add_lang(data_for_lang, lang, spec, origin);
+        }
+    }
+    Some(config.defaults)
+}
+
+// Updates an existing language with the spec's fields or adds a new one
+// (which must have a name)
+fn add_lang(
+    data_for_lang: &mut HashMap<String, LangData>,
+    lang: String,
+    spec: LangSpec,
+    origin: String,
+) {
+    let lang_data = if let Some(mut lang_data) = data_for_lang.remove(&lang)
+    {
+        lang_data
+            .overrides
+            .push(format!("{origin} ({})", spec.fields().join(", ")));
+        lang_data
+    } else {
+        let name = spec.name.clone().unwrap_or_else(|| lang.clone());
+        let mut lang_data = LangData::new(&name, HashSet::new(), &[], &[]);
+        lang_data.origin = origin;
+        lang_data
+    };
+    data_for_lang.insert(lang, apply_spec(lang_data, spec));
+}
+
+impl LangSpec {
+    // The names of the fields that are set
+    fn fields(&self) -> Vec<&'static str> {
+        [
+            ("name", self.name.is_some()),
+            ("exts", self.exts.is_some()),
+            ("filenames", self.filenames.is_some()),
+            ("interpreters", self.interpreters.is_some()),
+            ("line_comments", self.line_comments.is_some()),
+            ("block_comments", self.block_comments.is_some()),
+            ("nested_comments", self.nested_comments.is_some()),
+            ("strings", self.strings.is_some()),
+            ("raw_strings", self.raw_strings.is_some()),
+            ("multiline_strings", self.multiline_strings.is_some()),
+            ("char_literals", self.char_literals.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(field, is_set)| is_set.then_some(field))
+        .collect()
+    }
+}
+
+fn apply_spec(mut lang_data: LangData, spec: LangSpec) -> LangData {
+    if let Some(name) = spec.name {
+        lang_data.name = name;
+    }
+    if let Some(exts) = spec.exts {
+        lang_data.exts = exts
+            .iter()
+            .map(|ext| ext.strip_prefix('.').unwrap_or(ext).to_string())
+            .collect();
+    }
+    if let Some(line_comments) = spec.line_comments {
+        lang_data.line_comments = line_comments;
+    }
+    if let Some(block_comments) = spec.block_comments {
+        lang_data.block_comments = block_comments;
+    }
+    if let Some(nested_comments) = spec.nested_comments {
+        lang_data.nested_comments = nested_comments;
+    }
+    if let Some(strings) = spec.strings {
+        lang_data.strings = strings;
+    }
+    if let Some(raw_strings) = spec.raw_strings {
+        lang_data.raw_strings = raw_strings;
+    }
+    if let Some(multiline_strings) = spec.multiline_strings {
+        lang_data.multiline_strings = multiline_strings;
+    }
+    if let Some(char_literals) = spec.char_literals {
+        lang_data.char_literals = char_literals;
+    }
+    if let Some(interpreters) = spec.interpreters {
+        lang_data.interpreters = interpreters.into_iter().collect();
+    }
+    if let Some(filenames) = spec.filenames {
+        let filenames: Vec<&str> =
+            filenames.iter().map(|f| f.as_str()).collect();
+        lang_data = lang_data.with_filenames(&filenames);
+    }
+    lang_data
+}
+
+// Returns the 1-based line number of the given byte offset
+fn line_for(text: &str, offset: usize) -> usize {
+    let offset = offset.min(text.len());
+    text.as_bytes()[..offset].iter().filter(|&&b| b == b'\n').count() + 1
+}
+
+fn line_for_table(text: &str, lang: &str) -> usize {
+    let header = format!("[languages.{lang}]");
+    text.lines().position(|line| line.trim() == header).map_or(0, |i| i + 1)
+}
+
+// Generated by build.rs from languages.toml
+pub fn initial_data_for_lang() -> HashMap<String, LangData> {
+    include!(concat!(env!("OUT_DIR"), "/languages.rs"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dat_line_with_every_field() {
+        let (lang, spec) =
+            parse_dat_line("xl|Xl|xl xli|// #|/* */ (* *)|xl xlr|Xlfile")
+                .unwrap();
+        assert_eq!(lang, "xl");
+        assert_eq!(spec.name.as_deref(), Some("Xl"));
+        assert_eq!(spec.exts, Some(vec!["xl".into(), "xli".into()]));
+        assert_eq!(spec.line_comments, Some(vec!["//".into(), "#".into()]));
+        assert_eq!(
+            spec.block_comments,
+            Some(vec![
+                ("/*".into(), "*/".into()),
+                ("(*".into(), "*)".into())
+            ])
+        );
+        assert_eq!(
+            spec.interpreters,
+            Some(vec!["xl".into(), "xlr".into()])
+        );
+        assert_eq!(spec.filenames, Some(vec!["Xlfile".into()]));
+    }
+
+    #[test]
+    fn dat_line_leaves_missing_fields_unset() {
+        let (lang, spec) = parse_dat_line("py|Python|py pyi||").unwrap();
+        assert_eq!(lang, "py");
+        assert_eq!(spec.fields(), ["name", "exts"]);
+        let (_, spec) = parse_dat_line("md|Markdown|").unwrap();
+        assert_eq!(spec.exts, Some(vec![]));
+    }
+
+    #[test]
+    fn invalid_dat_lines() {
+        assert!(parse_dat_line("py|Python").is_err());
+        assert!(parse_dat_line("c|C|c h|//|/* */ {").is_err());
+    }
+}
@@ -1,16 +1,23 @@
 // Copyright © 2022 Mark Summerfield. All rights reserved.
 // License: GPLv3
 
+use crate::heuristics::{self, Heuristic};
 use crate::types::LangData;
 use state::Storage;
 use std::collections::HashMap;
 
 pub const FILE_COUNT_WIDTH: usize = 7;
 pub const LINE_COUNT_WIDTH: usize = 11;
+pub const COUNT_WIDTH: usize = 9;
+pub const MIN_FILENAME_WIDTH: usize = 20;
+pub const IGNORE_FILENAME: &str = ".clcignore";
+pub const SHEBANG_PREFIX_SIZE: usize = 512;
+pub const HEURISTIC_PREFIX_SIZE: usize = 16384;
 
 pub static EXCLUDE: Storage<Vec<&str>> = Storage::new();
 pub static DATA_FOR_LANG: Storage<HashMap<String, LangData>> =
     Storage::new();
+pub static HEURISTICS: Storage<Vec<Heuristic>> = Storage::new();
 
 pub fn initialize() {
     EXCLUDE.set(vec![
@@ -22,4 +29,5 @@ pub fn initialize() {
         "setup.py",
         "target",
     ]);
+    HEURISTICS.set(heuristics::initial_heuristics());
 }
@@ -0,0 +1,361 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use crate::types::{LangData, LineCounts};
+use std::cmp::Reverse;
+
+// A line counts as code if it has any non-whitespace outside of comments,
+// as a comment if it has a comment but no code, and as blank otherwise.
+// Delimiters are matched on raw bytes so the text need not be UTF-8.
+pub fn count_lines(
+    bytes: &[u8],
+    lang_data: Option<&LangData>,
+) -> LineCounts {
+    let mut counts = LineCounts::default();
+    let mut lexer = lang_data.map(Lexer::new);
+    let mut lines = bytes.split(|&b| b == b'\n').peekable();
+    while let Some(line) = lines.next() {
+        if line.is_empty() && lines.peek().is_none() {
+            break; // The text ended with a newline
+        }
+        if is_blank(line) {
+            counts.blanks += 1;
+            continue;
+        }
+        let (has_code, has_comment) = if let Some(lexer) = &mut lexer {
+            lexer.scan_line(line)
+        } else {
+            (true, false)
+        };
+        if has_code {
+            counts.code += 1;
+        } else if has_comment {
+            counts.comments += 1;
+        } else {
+            counts.blanks += 1; // e.g., only whitespace after a comment end
+        }
+    }
+    counts
+}
+
+// Returns whether each line counts as code, with the same rules as
+// count_lines, e.g., so that the lines changed between two versions of a
+// file can be classified
+pub fn code_lines(bytes: &[u8], lang_data: Option<&LangData>) -> Vec<bool> {
+    let mut code = vec![];
+    let mut lexer = lang_data.map(Lexer::new);
+    let mut lines = bytes.split(|&b| b == b'\n').peekable();
+    while let Some(line) = lines.next() {
+        if line.is_empty() && lines.peek().is_none() {
+            break; // The text ended with a newline
+        }
+        code.push(
+            !is_blank(line)
+                && lexer
+                    .as_mut()
+                    .is_none_or(|lexer| lexer.scan_line(line).0),
+        );
+    }
+    code
+}
+
+#[derive(Clone, Copy)]
+enum Token<'a> {
+    LineComment,
+    BlockComment(&'a str, &'a str, bool), // start, end, nests
+    Str(&'a str, bool, bool),             // end, has escapes, spans lines
+}
+
+enum State<'a> {
+    Code,
+    Comment { start: &'a str, end: &'a str, nests: bool, depth: usize },
+    Str { end: &'a str, escapes: bool, spans: bool },
+}
+
+// The state persists from line to line since comments and strings may
+// span lines. Strings with a one character start (other than a backtick)
+// end at the end of the line unless the language says otherwise, so that,
+// e.g., an apostrophe in a YAML value or a shell heredoc doesn't turn the
+// lines that follow into code.
+struct Lexer<'a> {
+    tokens: Vec<(&'a str, Token<'a>)>,
+    first_bytes: [bool; 256],
+    char_literals: bool,
+    state: State<'a>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(lang_data: &'a LangData) -> Self {
+        let mut tokens = vec![];
+        for start in &lang_data.line_comments {
+            tokens.push((start.as_str(), Token::LineComment));
+        }
+        for (start, end) in &lang_data.block_comments {
+            tokens.push((
+                start.as_str(),
+                Token::BlockComment(start, end, false),
+            ));
+        }
+        for (start, end) in &lang_data.nested_comments {
+            tokens.push((
+                start.as_str(),
+                Token::BlockComment(start, end, true),
+            ));
+        }
+        let spans = |start: &str| {
+            start.len() > 1
+                || start == "`"
+                || lang_data.multiline_strings.iter().any(|s| s == start)
+        };
+        for (start, end) in &lang_data.strings {
+            tokens.push((
+                start.as_str(),
+                Token::Str(end, true, spans(start)),
+            ));
+        }
+        for (start, end) in &lang_data.raw_strings {
+            tokens.push((
+                start.as_str(),
+                Token::Str(end, false, spans(start)),
+            ));
+        }
+        tokens.retain(|(start, _)| !start.is_empty());
+        // Longest first so that, e.g., `"""` is preferred to `"` and `#=`
+        // to `#`
+        tokens.sort_by_key(|(start, _)| Reverse(start.len()));
+        let mut first_bytes = [false; 256];
+        for (start, _) in &tokens {
+            first_bytes[start.as_bytes()[0] as usize] = true;
+        }
+        Self {
+            tokens,
+            first_bytes,
+            char_literals: lang_data.char_literals,
+            state: State::Code,
+        }
+    }
+
+    // Returns whether the line has code and whether it has a comment
+    fn scan_line(&mut self, line: &[u8]) -> (bool, bool) {
+        let mut has_code = false;
+        let mut has_comment = false;
+        let mut i = 0;
+        while i < line.len() {
+            match self.state {
+                State::Comment { start, end, nests, depth } => {
+                    has_comment = true;
+                    if line[i..].starts_with(end.as_bytes()) {
+                        i += end.len();
+                        self.state = if depth == 1 {
+                            State::Code
+                        } else {
+                            State::Comment {
+                                start,
+                                end,
+                                nests,
+                                depth: depth - 1,
+                            }
+                        };
+                    } else if nests
+                        && line[i..].starts_with(start.as_bytes())
+                    {
+                        i += start.len();
+                        self.state = State::Comment {
+                            start,
+                            end,
+                            nests,
+                            depth: depth + 1,
+                        };
+                    } else {
+                        i += 1;
+                    }
+                }
+                State::Str { end, escapes, .. } => {
+                    has_code = true;
+                    if escapes && line[i] == b'\\' {
+                        i += 2;
+                    } else if line[i..].starts_with(end.as_bytes()) {
+                        i += end.len();
+                        self.state = State::Code;
+                    } else {
+                        i += 1;
+                    }
+                }
+                State::Code => {
+                    let b = line[i];
+                    if b.is_ascii_whitespace() {
+                        i += 1;
+                        continue;
+                    }
+                    if self.first_bytes[b as usize] {
+                        if let Some((start, token)) = self.token_at(line, i)
+                        {
+                            i += start.len();
+                            match token {
+                                Token::LineComment => {
+                                    has_comment = true;
+                                    break;
+                                }
+                                Token::BlockComment(start, end, nests) => {
+                                    has_comment = true;
+                                    self.state = State::Comment {
+                                        start,
+                                        end,
+                                        nests,
+                                        depth: 1,
+                                    };
+                                }
+                                Token::Str(end, escapes, spans) => {
+                                    has_code = true;
+                                    self.state =
+                                        State::Str { end, escapes, spans };
+                                }
+                            }
+                            continue;
+                        }
+                    }
+                    has_code = true;
+                    i += if self.char_literals && b == b'\'' {
+                        char_literal_size(&line[i..])
+                    } else {
+                        1
+                    };
+                }
+            }
+        }
+        if let State::Str { spans: false, .. } = self.state {
+            self.state = State::Code;
+        }
+        (has_code, has_comment)
+    }
+
+    fn token_at(
+        &self,
+        line: &[u8],
+        i: usize,
+    ) -> Option<(&'a str, Token<'a>)> {
+        // A delimiter that starts with a letter (e.g., a raw string
+        // prefix) doesn't match at the end of an identifier
+        let after_ident = i > 0
+            && (line[i - 1].is_ascii_alphanumeric() || line[i - 1] == b'_');
+        self.tokens
+            .iter()
+            .find(|(start, _)| {
+                line[i..].starts_with(start.as_bytes())
+                    && !(after_ident
+                        && start.as_bytes()[0].is_ascii_alphabetic())
+            })
+            .copied()
+    }
+}
+
+// Returns the size of the char literal at the start of text, or 1 if
+// there isn't one (e.g., for a Rust lifetime or a transpose operator).
+fn char_literal_size(text: &[u8]) -> usize {
+    match text.get(1) {
+        Some(b'\\') => {
+            // Skip the escaped char then look for the closing quote
+            if let Some(j) =
+                text.iter().skip(3).take(10).position(|&b| b == b'\'')
+            {
+                j + 4
+            } else {
+                1
+            }
+        }
+        Some(&b) => {
+            let size = utf8_size(b);
+            if text.get(1 + size) == Some(&b'\'') {
+                size + 2
+            } else {
+                1
+            }
+        }
+        None => 1,
+    }
+}
+
+fn utf8_size(b: u8) -> usize {
+    match b {
+        0xF0..=0xF7 => 4,
+        0xE0..=0xEF => 3,
+        0xC0..=0xDF => 2,
+        _ => 1,
+    }
+}
+
+fn is_blank(text: &[u8]) -> bool {
+    text.iter().all(|b| b.is_ascii_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn rust() -> LangData {
+        LangData::new("Rust", HashSet::from(["rs"]), &["//"], &[])
+            .with_nested_comments(&[("/*", "*/")])
+            .with_raw_strings(&[("r\"", "\""), ("r#\"", "\"#")])
+            .with_char_literals()
+            .with_multiline_strings(&["\""])
+    }
+
+    fn yaml() -> LangData {
+        LangData::new("YAML", HashSet::from(["yaml"]), &["#"], &[])
+            .with_strings(&[("\"", "\""), ("'", "'")])
+    }
+
+    fn counts(text: &str, lang_data: &LangData) -> (usize, usize, usize) {
+        let counts = count_lines(text.as_bytes(), Some(lang_data));
+        (counts.code, counts.comments, counts.blanks)
+    }
+
+    #[test]
+    fn nested_comments() {
+        let text = "/* a /* b */ still a comment\n*/\n\nlet x = 1;\n";
+        assert_eq!(counts(text, &rust()), (1, 2, 1));
+    }
+
+    #[test]
+    fn comment_start_in_string() {
+        let text = "let s = \"/* not a comment\";\nlet t = 1;\n";
+        assert_eq!(counts(text, &rust()), (2, 0, 0));
+        let text = "let s = r#\"\"/*\"#; // comment\n// comment\n";
+        assert_eq!(counts(text, &rust()), (1, 1, 0));
+    }
+
+    #[test]
+    fn multiline_string() {
+        let text = "let s = \"a\n// not a comment\n\";\n// comment\n";
+        assert_eq!(counts(text, &rust()), (3, 1, 0));
+    }
+
+    #[test]
+    fn lifetimes_and_char_literals() {
+        let text = "fn f<'a>(s: &'a str) -> char { '\"' }\n// comment\n";
+        assert_eq!(counts(text, &rust()), (1, 1, 0));
+        assert_eq!(char_literal_size(b"'a'"), 3);
+        assert_eq!(char_literal_size(b"'\\n'"), 4);
+        assert_eq!(char_literal_size(b"'\\u{1F600}'"), 11);
+        assert_eq!(char_literal_size("'é'".as_bytes()), 4);
+        assert_eq!(char_literal_size(b"'a>(s: &'a str)"), 1);
+        assert_eq!(char_literal_size(b"'"), 1);
+    }
+
+    #[test]
+    fn unclosed_quote_ends_at_end_of_line() {
+        let text = "msg: don't\n# one\n# two\n";
+        assert_eq!(counts(text, &yaml()), (1, 2, 0));
+    }
+
+    #[test]
+    fn code_lines_match_count_lines() {
+        let text = "/* a\n*/\n\nx = 1 // c\n// d\n";
+        assert_eq!(
+            code_lines(text.as_bytes(), Some(&rust())),
+            [false, false, false, true, false]
+        );
+        assert_eq!(code_lines(b"a\n\nb", None), [true, false, true]);
+    }
+}
@@ -0,0 +1,85 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use crate::config::Config;
+use crate::report::Report;
+use crate::types::FileData;
+
+// Outputs CSV (if sep is ',') or TSV (if sep is '\t') with a header row and
+// raw integers (i.e., no thousands separators) for easy importing. The last
+// row is the grand total and has an empty lang.
+pub fn display(file_data: Vec<FileData>, config: Config, sep: char) {
+    let report = Report::new(file_data, &config);
+    let row = |fields: &[&str]| {
+        let fields: Vec<String> =
+            fields.iter().map(|field| escape(field, sep)).collect();
+        println!("{}", fields.join(&sep.to_string()));
+    };
+    if let Some(files) = &report.files {
+        row(&[
+            "lang", "name", "filename", "lines", "code", "comments",
+            "blanks",
+        ]);
+        for file_datum in files {
+            let name = report.name_for_lang(&file_datum.lang);
+            row(&[
+                &file_datum.lang,
+                name,
+                &file_datum.filename,
+                &file_datum.lines.to_string(),
+                &file_datum.code.to_string(),
+                &file_datum.comments.to_string(),
+                &file_datum.blanks.to_string(),
+            ]);
+        }
+        let total = &report.total;
+        row(&[
+            "",
+            "Total",
+            "",
+            &total.lines.to_string(),
+            &total.code.to_string(),
+            &total.comments.to_string(),
+            &total.blanks.to_string(),
+        ]);
+    } else {
+        row(&[
+            "lang", "name", "files", "lines", "code", "comments", "blanks",
+            "percent",
+        ]);
+        for lang_total in &report.languages {
+            row(&[
+                &lang_total.lang,
+                &lang_total.name,
+                &lang_total.files.to_string(),
+                &lang_total.lines.to_string(),
+                &lang_total.code.to_string(),
+                &lang_total.comments.to_string(),
+                &lang_total.blanks.to_string(),
+                &lang_total.percent.to_string(),
+            ]);
+        }
+        let total = &report.total;
+        row(&[
+            "",
+            "Total",
+            &total.files.to_string(),
+            &total.lines.to_string(),
+            &total.code.to_string(),
+            &total.comments.to_string(),
+            &total.blanks.to_string(),
+            "100",
+        ]);
+    }
+}
+
+fn escape(field: &str, sep: char) -> String {
+    if sep == '\t' {
+        // TSV fields can't contain tabs or newlines so we replace them
+        field.replace(['\t', '\n', '\r'], " ")
+    } else if field.contains([sep, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,445 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use crate::config::Config;
+use crate::consts;
+use crate::count;
+use crate::display;
+use crate::git;
+use crate::lang;
+use crate::types::Skipped;
+use crate::valid;
+use crate::walk;
+use git2::{
+    Blob, Delta, DiffFile, DiffOptions, FileMode, Patch, Repository,
+};
+use num_format::{SystemLocale, ToFormattedString};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+// Unlike a baseline's line changes, these come from comparing the files'
+// code lines, so lines can be modified as well as added and removed
+#[derive(Clone, Copy, Debug, Default)]
+struct CodeChange {
+    files: usize,
+    added: usize,
+    removed: usize,
+    modified: usize,
+}
+
+impl CodeChange {
+    fn add(&mut self, other: &CodeChange) {
+        self.files += other.files;
+        self.added += other.added;
+        self.removed += other.removed;
+        self.modified += other.modified;
+    }
+
+    fn total(&self) -> usize {
+        self.added + self.removed + self.modified
+    }
+}
+
+struct FileChange {
+    lang: String,
+    name: String,
+    status: char, // A, D, M, or R as for git diff --name-status
+    change: CodeChange,
+}
+
+// Compares the trees of the two revisions without checking out. Only the
+// files that would be counted in the newer revision (or, for those that
+// were deleted, the older one) are compared.
+pub fn display(rev1: &str, rev2: &str, config: &Config) -> Vec<Skipped> {
+    let roots: Vec<PathBuf> =
+        config.files.iter().map(|name| git::absolute(name)).collect();
+    let repo = git::open_repo(&roots[0]);
+    let workdir = git::workdir(&repo);
+    let (file_changes, skipped) =
+        get_file_changes(&repo, rev1, rev2, &workdir, &roots, config);
+    let locale = SystemLocale::default().unwrap();
+    display_langs(&file_changes, &locale, config.sortbylines);
+    if !config.summary {
+        display_files(file_changes, &locale, config.sortbylines);
+    }
+    skipped
+}
+
+fn get_file_changes(
+    repo: &Repository,
+    rev1: &str,
+    rev2: &str,
+    workdir: &Path,
+    roots: &[PathBuf],
+    config: &Config,
+) -> (Vec<FileChange>, Vec<Skipped>) {
+    let old_tree = git::tree_for_rev(repo, rev1);
+    let new_tree = git::tree_for_rev(repo, rev2);
+    let mut old_ignores = git::RevIgnores::new(repo, &old_tree, config);
+    let mut new_ignores = git::RevIgnores::new(repo, &new_tree, config);
+    let mut file_changes = vec![];
+    let mut skipped = vec![];
+    let mut options = DiffOptions::new();
+    options
+        .context_lines(0)
+        .ignore_submodules(true)
+        .disable_pathspec_match(true);
+    let mut whole_tree = false;
+    for root in roots {
+        match root.strip_prefix(workdir) {
+            Ok(prefix) if prefix.as_os_str().is_empty() => {
+                whole_tree = true
+            }
+            Ok(prefix) => {
+                options.pathspec(prefix);
+            }
+            Err(_) => {
+                skipped.push(Skipped::new(root, "not in the repository"))
+            }
+        }
+    }
+    if whole_tree {
+        options = DiffOptions::new();
+        options.context_lines(0).ignore_submodules(true);
+    }
+    let diff = match repo.diff_tree_to_tree(
+        Some(&old_tree),
+        Some(&new_tree),
+        Some(&mut options),
+    ) {
+        Ok(mut diff) => {
+            // So renamed files with small edits count as modified rather
+            // than as wholly removed and added
+            if let Err(err) = diff.find_similar(None) {
+                skipped.push(Skipped::new(workdir, err.message()));
+            }
+            diff
+        }
+        Err(err) => {
+            skipped.push(Skipped::new(workdir, err.message()));
+            return (file_changes, skipped);
+        }
+    };
+    for (i, delta) in diff.deltas().enumerate() {
+        let status = match delta.status() {
+            Delta::Added | Delta::Copied => 'A',
+            Delta::Deleted => 'D',
+            Delta::Modified | Delta::Typechange => 'M',
+            Delta::Renamed => 'R',
+            _ => continue,
+        };
+        let file =
+            if status == 'D' { delta.old_file() } else { delta.new_file() };
+        if matches!(file.mode(), FileMode::Link | FileMode::Commit) {
+            continue;
+        }
+        let Some(path) = file.path() else {
+            continue;
+        };
+        let filename = workdir.join(path);
+        let Some((root, index, explicit)) = root_for(&filename, roots)
+        else {
+            continue;
+        };
+        let ignores =
+            if status == 'D' { &mut old_ignores } else { &mut new_ignores };
+        if !is_counted(&filename, root, explicit, ignores, config) {
+            continue;
+        }
+        let mut name =
+            walk::report_name(&filename, root, index, explicit, config);
+        if status == 'R' {
+            if let Some(old_path) = delta.old_file().path() {
+                let old_name = walk::report_name(
+                    &workdir.join(old_path),
+                    root,
+                    index,
+                    explicit,
+                    config,
+                );
+                name = format!("{name} (from {old_name})");
+            }
+        }
+        let (old_blob, new_blob) = match (
+            find_blob(repo, &delta.old_file()),
+            find_blob(repo, &delta.new_file()),
+        ) {
+            (Ok(old_blob), Ok(new_blob)) => (old_blob, new_blob),
+            (Err(err), _) | (_, Err(err)) => {
+                skipped.push(Skipped::new(Path::new(&name), err.message()));
+                continue;
+            }
+        };
+        let old_bytes = old_blob.as_ref().map_or(&[][..], |b| b.content());
+        let new_bytes = new_blob.as_ref().map_or(&[][..], |b| b.content());
+        let bytes = if status == 'D' { old_bytes } else { new_bytes };
+        let lang = lang::lang_for_file(&filename, bytes, &config.langs);
+        let lang_data = lang::lang_data_for(&lang);
+        let old_code = count::code_lines(old_bytes, lang_data);
+        let new_code = count::code_lines(new_bytes, lang_data);
+        match get_change(&diff, i, &old_code, &new_code) {
+            Ok(Some(change)) => {
+                file_changes.push(FileChange { lang, name, status, change })
+            }
+            Ok(None) => {} // Binary
+            Err(err) => {
+                skipped.push(Skipped::new(Path::new(&name), err.message()))
+            }
+        }
+    }
+    (file_changes, skipped)
+}
+
+// Added and deleted files have no blob on one side
+fn find_blob<'a>(
+    repo: &'a Repository,
+    file: &DiffFile,
+) -> Result<Option<Blob<'a>>, git2::Error> {
+    if file.id().is_zero() {
+        Ok(None)
+    } else {
+        repo.find_blob(file.id()).map(Some)
+    }
+}
+
+// Only code lines count, so, e.g., adding a comment doesn't add lines.
+// Within each hunk as many code lines as were both removed and added count
+// as modified (as for cloc's --diff), and the rest as added or removed.
+fn get_change(
+    diff: &git2::Diff,
+    index: usize,
+    old_code: &[bool],
+    new_code: &[bool],
+) -> Result<Option<CodeChange>, git2::Error> {
+    let Some(patch) = Patch::from_diff(diff, index)? else {
+        return Ok(None);
+    };
+    if patch.delta().flags().is_binary() {
+        return Ok(None);
+    }
+    let mut change = CodeChange { files: 1, ..Default::default() };
+    for i in 0..patch.num_hunks() {
+        let (hunk, _) = patch.hunk(i)?;
+        let added = code_in(new_code, hunk.new_start(), hunk.new_lines());
+        let removed = code_in(old_code, hunk.old_start(), hunk.old_lines());
+        let modified = added.min(removed);
+        change.modified += modified;
+        change.added += added - modified;
+        change.removed += removed - modified;
+    }
+    Ok(Some(change))
+}
+
+// Returns how many of the hunk's lines (whose start is 1-based) are code
+fn code_in(code: &[bool], start: u32, lines: u32) -> usize {
+    code.iter()
+        .skip((start as usize).saturating_sub(1))
+        .take(lines as usize)
+        .filter(|&&is_code| is_code)
+        .count()
+}
+
+// Returns the first root (in the order given) that is or contains the
+// filename, its index, and whether it is the file itself
+fn root_for<'a>(
+    filename: &'a Path,
+    roots: &'a [PathBuf],
+) -> Option<(&'a Path, usize, bool)> {
+    roots.iter().enumerate().find_map(|(index, root)| {
+        if root == filename {
+            Some((filename.parent().unwrap_or(filename), index, true))
+        } else if filename.starts_with(root) {
+            Some((root.as_path(), index, false))
+        } else {
+            None
+        }
+    })
+}
+
+// The same rules as when counting, with the ignore files from the
+// revision the file is in
+fn is_counted(
+    filename: &Path,
+    root: &Path,
+    explicit: bool,
+    ignores: &mut git::RevIgnores,
+    config: &Config,
+) -> bool {
+    if !explicit
+        && (filename
+            .ancestors()
+            .skip(1)
+            .take_while(|folder| *folder != root)
+            .any(|folder| {
+                !valid::is_valid_dir(folder, root, config)
+                    || ignores.is_ignored(folder, true)
+            })
+            || ignores.is_ignored(filename, false))
+    {
+        return false;
+    }
+    valid::file_verdict(filename, root, config).is_valid()
+}
+
+fn display_langs(
+    file_changes: &[FileChange],
+    locale: &SystemLocale,
+    sortbylines: bool,
+) {
+    let mut change_for_lang = HashMap::<&str, CodeChange>::new();
+    let mut total = CodeChange::default();
+    for file_change in file_changes {
+        total.add(&file_change.change);
+        change_for_lang
+            .entry(file_change.lang.as_str())
+            .or_default()
+            .add(&file_change.change);
+    }
+    let mut rows: Vec<(&str, CodeChange)> = change_for_lang
+        .into_iter()
+        .filter_map(|(lang, change)| {
+            display::row_name(lang).map(|name| (name, change))
+        })
+        .collect();
+    if sortbylines {
+        rows.sort_by_key(|(name, change)| {
+            (change.total(), name.to_lowercase())
+        });
+    } else {
+        rows.sort_by_key(|(name, _)| name.to_lowercase());
+    }
+    let name_width =
+        display::name_width(rows.iter().map(|(name, _)| *name));
+    let count_width = consts::FILE_COUNT_WIDTH;
+    println!("{:name_width$} {:>count_width$}{}", "", "files", titles());
+    for (name, change) in &rows {
+        let files = change.files.to_formatted_string(locale);
+        println!(
+            "{name:name_width$} {files:>count_width$}{}",
+            format_change(change, locale)
+        );
+    }
+    let row_width = name_width + count_width + 1 + get_columns_width();
+    display::rule(row_width);
+    let files = total.files.to_formatted_string(locale);
+    println!(
+        "{:name_width$} {files:>count_width$}{}",
+        "Total",
+        format_change(&total, locale)
+    );
+}
+
+fn display_files(
+    mut file_changes: Vec<FileChange>,
+    locale: &SystemLocale,
+    sortbylines: bool,
+) {
+    if file_changes.is_empty() {
+        return;
+    }
+    if sortbylines {
+        file_changes.sort_by(|a, b| {
+            a.change
+                .total()
+                .cmp(&b.change.total())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+    } else {
+        file_changes.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+    println!();
+    println!("status{} file", titles());
+    for file_change in file_changes {
+        println!(
+            "{:<6}{} {}",
+            file_change.status,
+            format_change(&file_change.change, locale),
+            file_change.name
+        );
+    }
+}
+
+fn titles() -> String {
+    let count_width = consts::COUNT_WIDTH;
+    format!(
+        " {:>count_width$} {:>count_width$} {:>count_width$}",
+        "added", "removed", "modified"
+    )
+}
+
+fn format_change(change: &CodeChange, locale: &SystemLocale) -> String {
+    let count_width = consts::COUNT_WIDTH;
+    format!(
+        " {:>count_width$} {:>count_width$} {:>count_width$}",
+        change.added.to_formatted_string(locale),
+        change.removed.to_formatted_string(locale),
+        change.modified.to_formatted_string(locale)
+    )
+}
+
+fn get_columns_width() -> usize {
+    3 * (consts::COUNT_WIDTH + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(
+        patch: &str,
+        old_code: &[bool],
+        new_code: &[bool],
+    ) -> CodeChange {
+        let diff = git2::Diff::from_buffer(patch.as_bytes()).unwrap();
+        get_change(&diff, 0, old_code, new_code).unwrap().unwrap()
+    }
+
+    #[test]
+    fn modified_lines() {
+        // Line 2 (code) and line 3 (a comment) are replaced by three code
+        // lines and a comment
+        let patch = "\
+diff --git a/a.go b/a.go
+--- a/a.go
++++ b/a.go
+@@ -2,2 +2,4 @@
+-x := 1
+-// old
++x := 2
++y := 3
++z := 4
++// new
+";
+        let change = change(
+            patch,
+            &[true, true, false],
+            &[true, true, true, true, false],
+        );
+        assert_eq!(
+            (change.files, change.added, change.removed, change.modified),
+            (1, 2, 0, 1)
+        );
+    }
+
+    #[test]
+    fn only_code_lines_count() {
+        let patch = "\
+diff --git a/a.go b/a.go
+--- a/a.go
++++ b/a.go
+@@ -1,0 +2,2 @@
++// comment
++
+@@ -3 +4,0 @@
+-y := 1
+";
+        let change =
+            change(patch, &[true, true, true], &[true, false, false, true]);
+        assert_eq!(
+            (change.added, change.removed, change.modified),
+            (0, 1, 0)
+        );
+    }
+}
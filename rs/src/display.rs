@@ -3,25 +3,36 @@
 
 use crate::config::Config;
 use crate::consts;
-use crate::types::FileData;
+use crate::report::{self, LangTotal, Report, Total};
+use crate::types::{FileData, LineCounts, Skipped};
 use crate::util;
 use num_format::{SystemLocale, ToFormattedString};
-use std::{cmp::Ordering, collections::HashMap, time::Instant};
+use std::time::Instant;
 
-type NForLang = HashMap<String, usize>;
+const PERCENT_WIDTH: usize = 6;
+const NO_LANG_NAME: &str = "No language"; // e.g., for files given with -i
 
 pub fn display_summary(
     file_data: Vec<FileData>,
     config: Config,
     t: Instant,
 ) {
-    let lang_width = get_lang_width();
-    let (total_for_lang, count_for_lang) = get_maps(&file_data);
-    let totals = get_sorted_totals(&total_for_lang, config.sortbylines);
-    for (lang, total) in totals {
-        if let Some(count) = count_for_lang.get(lang) {
-            display_summary_line(*count, total, lang, lang_width);
+    let report = Report::new(file_data, &config);
+    let lang_width = get_lang_width(&report.languages);
+    if !report.languages.is_empty() {
+        let prefix_width = lang_width + consts::FILE_COUNT_WIDTH + 7;
+        display_column_titles(prefix_width);
+        println!(" {:>PERCENT_WIDTH$}", "%");
+        for lang_total in &report.languages {
+            display_summary_line(lang_total, lang_width);
         }
+        let row_width = prefix_width + get_columns_width();
+        #[cfg(windows)]
+        println!("{}", "-".repeat(row_width + PERCENT_WIDTH + 1));
+        #[cfg(unix)]
+        println!("{}", "─".repeat(row_width + PERCENT_WIDTH + 1));
+        display_total(&report.total, row_width);
+        println!(" {:>PERCENT_WIDTH$}", "100.0%");
     }
     let secs = t.elapsed().as_secs_f32();
     if secs > 0.1 {
@@ -29,128 +40,115 @@ pub fn display_summary(
     }
 }
 
-fn get_lang_width() -> usize {
-    consts::DATA_FOR_LANG
-        .get()
-        .values()
-        .map(|v| v.name.len())
+// Only the languages being reported matter since there are hundreds
+fn get_lang_width(languages: &[LangTotal]) -> usize {
+    languages
+        .iter()
+        .map(|lang_total| lang_total.name.chars().count())
         .reduce(|acc, value| if acc >= value { acc } else { value })
         .unwrap_or(10)
 }
 
-fn get_maps(file_data: &[FileData]) -> (NForLang, NForLang) {
-    let mut total_for_lang = NForLang::new();
-    let mut count_for_lang = NForLang::new();
-    for file_datum in file_data {
-        total_for_lang
-            .entry(file_datum.lang.clone())
-            .and_modify(|lines| *lines += file_datum.lines)
-            .or_insert(file_datum.lines);
-        count_for_lang
-            .entry(file_datum.lang.clone())
-            .and_modify(|count| *count += 1)
-            .or_insert(1);
-    }
-    (total_for_lang, count_for_lang)
-}
-
-fn get_sorted_totals(
-    total_for_lang: &NForLang,
-    sortbylines: bool,
-) -> Vec<(&str, usize)> {
-    let mut totals: Vec<(&str, usize)> = total_for_lang
-        .iter()
-        .map(|(lang, lines)| (lang.as_str(), *lines))
-        .collect();
-    if sortbylines {
-        totals.sort_by(|a, b| {
-            let (alang, alines) = a;
-            let (blang, blines) = b;
-            if alines != blines {
-                alines.partial_cmp(blines).unwrap_or(Ordering::Equal)
-            } else {
-                alang
-                    .to_lowercase()
-                    .partial_cmp(&blang.to_lowercase())
-                    .unwrap_or(Ordering::Equal)
-            }
-        });
-    } else {
-        totals.sort_by(|a, b| {
-            let (alang, alines) = a;
-            let (blang, blines) = b;
-            alang
-                .to_lowercase()
-                .partial_cmp(&blang.to_lowercase())
-                .unwrap_or_else(|| {
-                    alines.partial_cmp(blines).unwrap_or(Ordering::Equal)
-                })
-        });
-    }
-    totals
-}
-
-fn display_summary_line(
-    count: usize,
-    total: usize,
-    lang: &str,
-    lang_width: usize,
-) {
+fn display_summary_line(lang_total: &LangTotal, lang_width: usize) {
     let locale = SystemLocale::default().unwrap();
     let count_width = consts::FILE_COUNT_WIDTH;
-    let lines_width = consts::LINE_COUNT_WIDTH;
+    let count = lang_total.files;
     let s = if count == 1 { ' ' } else { 's' };
-    if let Some(lang_data) = consts::DATA_FOR_LANG.get().get(lang) {
+    if consts::DATA_FOR_LANG.get().contains_key(lang_total.lang.as_str()) {
         let count = count.to_formatted_string(&locale);
-        let total = total.to_formatted_string(&locale);
-        let name = &lang_data.name;
+        let name = &lang_total.name;
+        let columns = format_columns(&lang_total.counts(), &locale);
+        let percent = format!("{:.1}%", lang_total.percent);
         println!(
-            "{name:lang_width$} {count:>count_width$} file{s} \
-            {total:>lines_width$} lines"
+            "{name:lang_width$} {count:>count_width$} file{s}{columns} \
+            {percent:>PERCENT_WIDTH$}"
         );
     }
 }
 
+// Callers add a newline or further titles
+pub fn display_column_titles(prefix_width: usize) {
+    let count_width = consts::COUNT_WIDTH;
+    let lines_width = consts::LINE_COUNT_WIDTH;
+    print!(
+        "{:prefix_width$} {:>count_width$} {:>count_width$} \
+        {:>count_width$} {:>lines_width$}",
+        "", "code", "comment", "blank", "lines"
+    );
+}
+
+pub fn get_columns_width() -> usize {
+    3 * (consts::COUNT_WIDTH + 1) + 1 + consts::LINE_COUNT_WIDTH
+}
+
+pub fn format_columns(
+    counts: &LineCounts,
+    locale: &SystemLocale,
+) -> String {
+    let count_width = consts::COUNT_WIDTH;
+    let lines_width = consts::LINE_COUNT_WIDTH;
+    let code = counts.code.to_formatted_string(locale);
+    let comments = counts.comments.to_formatted_string(locale);
+    let blanks = counts.blanks.to_formatted_string(locale);
+    let lines = counts.lines().to_formatted_string(locale);
+    format!(
+        " {code:>count_width$} {comments:>count_width$} \
+        {blanks:>count_width$} {lines:>lines_width$}"
+    )
+}
+
 pub fn display_full(mut file_data: Vec<FileData>, config: Config) {
     let locale = SystemLocale::default().unwrap();
     let filename_width = get_filename_width(&file_data, config.maxwidth);
-    let row_width = filename_width + 1 + consts::LINE_COUNT_WIDTH;
+    let row_width = filename_width + get_columns_width();
     let third = (filename_width / 3) - 1;
-    let lines_width = consts::LINE_COUNT_WIDTH;
-    let mut lang = String::new();
+    let mut lang: Option<String> = None;
     let mut count = 0;
-    let mut subtotal = 0;
-    sort_file_data(&mut file_data, config.sortbylines);
+    let mut subtotal = LineCounts::default();
+    let total = report::get_total(&file_data);
+    report::sort_file_data(&mut file_data, config.sortbylines);
+    if !file_data.is_empty() {
+        display_column_titles(filename_width);
+        println!();
+    }
     for file_datum in file_data {
-        if lang.is_empty() || lang != file_datum.lang {
-            (lang, count, subtotal) = display_new_lang(
-                &lang,
-                count,
-                subtotal,
-                row_width,
-                &file_datum,
-            );
+        // Files without a language sort first and get a group of their own
+        if lang.as_ref() != Some(&file_datum.lang) {
+            if let Some(lang) = &lang {
+                display_subtotal(lang, count, subtotal, &total, row_width);
+            }
+            display_lang_title(&file_datum.lang, row_width);
+            lang = Some(file_datum.lang.clone());
+            count = 0;
+            subtotal = LineCounts::default();
         }
         let filename =
             util::elide(&file_datum.filename, third, filename_width);
-        let lines = file_datum.lines.to_formatted_string(&locale);
-        println!("{filename:filename_width$} {lines: >lines_width$}");
-        subtotal += file_datum.lines;
+        let columns = format_columns(&file_datum.counts(), &locale);
+        println!("{filename:filename_width$}{columns}");
+        subtotal.add(&file_datum.counts());
         count += 1;
     }
-    if !lang.is_empty() {
-        display_subtotal(&lang, count, subtotal, row_width);
-        #[cfg(windows)]
-        println!("{}", "=".repeat(row_width));
-        #[cfg(unix)]
-        println!("{}", "━".repeat(row_width));
+    if let Some(lang) = lang {
+        display_subtotal(&lang, count, subtotal, &total, row_width);
+        rule(row_width);
+        display_total(&total, row_width);
+        println!();
     }
 }
 
 fn get_filename_width(file_data: &[FileData], maxwidth: usize) -> usize {
+    // Leave room for the subtotal's "Name (NN.N%)   N files" label
+    let label_width = |f: &FileData| {
+        lang_name(&f.lang).len()
+            + PERCENT_WIDTH
+            + 3
+            + consts::FILE_COUNT_WIDTH
+            + 7
+    };
     if let Some(width) = file_data
         .iter()
-        .map(|f| f.filename.len())
+        .map(|f| f.filename.len().max(label_width(f)))
         .reduce(|acc, value| if acc >= value { acc } else { value })
     {
         width
@@ -160,88 +158,87 @@ fn get_filename_width(file_data: &[FileData], maxwidth: usize) -> usize {
     .min(maxwidth)
 }
 
-fn sort_file_data(file_data: &mut [FileData], sortbylines: bool) {
-    if sortbylines {
-        file_data.sort_by(|a, b| {
-            if a.lang != b.lang {
-                a.lang.partial_cmp(&b.lang).unwrap_or(Ordering::Equal)
-            } else if a.lines != b.lines {
-                a.lines.partial_cmp(&b.lines).unwrap_or(Ordering::Equal)
-            } else {
-                a.lang
-                    .to_lowercase()
-                    .partial_cmp(&b.lang.to_lowercase())
-                    .unwrap_or(Ordering::Equal)
-            }
-        });
-    } else {
-        file_data.sort_by(|a, b| {
-            if a.lang != b.lang {
-                a.lang.partial_cmp(&b.lang).unwrap_or(Ordering::Equal)
-            } else {
-                a.filename
-                    .to_lowercase()
-                    .partial_cmp(&b.filename.to_lowercase())
-                    .unwrap_or_else(|| {
-                        a.lines
-                            .partial_cmp(&b.lines)
-                            .unwrap_or(Ordering::Equal)
-                    })
-            }
-        });
-    }
+fn lang_name(lang: &str) -> &str {
+    consts::DATA_FOR_LANG
+        .get()
+        .get(lang)
+        .map_or(NO_LANG_NAME, |lang_data| lang_data.name.as_str())
 }
 
-fn display_new_lang(
-    lang: &str,
-    count: usize,
-    subtotal: usize,
-    row_width: usize,
-    file_datum: &FileData,
-) -> (String, usize, usize) {
-    let mut zeros = false;
-    if !lang.is_empty() {
-        display_subtotal(lang, count, subtotal, row_width);
-        zeros = true;
-    }
-    let lang = &file_datum.lang;
-    if let Some(lang_data) = consts::DATA_FOR_LANG.get().get(lang.as_str())
-    {
-        let name = format!(" {} ", lang_data.name);
-        #[cfg(windows)]
-        println!("{name:=^row_width$}");
-        #[cfg(unix)]
-        println!("{name:━^row_width$}");
-    }
-    if zeros {
-        (lang.to_string(), 0, 0)
-    } else {
-        (lang.to_string(), count, subtotal)
-    }
+fn display_lang_title(lang: &str, row_width: usize) {
+    let name = format!(" {} ", lang_name(lang));
+    #[cfg(windows)]
+    println!("{name:=^row_width$}");
+    #[cfg(unix)]
+    println!("{name:━^row_width$}");
 }
 
 fn display_subtotal(
     lang: &str,
     count: usize,
-    subtotal: usize,
+    subtotal: LineCounts,
+    total: &Total,
     row_width: usize,
 ) {
-    if let Some(lang_data) = consts::DATA_FOR_LANG.get().get(lang) {
-        let name = &lang_data.name;
-        let locale = SystemLocale::default().unwrap();
-        #[cfg(windows)]
-        println!("{}", "-".repeat(row_width));
-        #[cfg(unix)]
-        println!("{}", "─".repeat(row_width));
-        let s = if count == 1 { ' ' } else { 's' };
-        let count = count.to_formatted_string(&locale);
-        let subtotal = subtotal.to_formatted_string(&locale);
-        let count_width = consts::FILE_COUNT_WIDTH;
-        let lines_width = consts::LINE_COUNT_WIDTH;
-        let numbers = format!(
-            "{count:>count_width$} file{s} {subtotal:>lines_width$} lines"
-        );
-        let width = (row_width - numbers.len()).min(10000);
-        println!("{name:<width$}{numbers}");
+    let percent = report::percent(subtotal.lines(), total.lines);
+    let name = format!("{} ({percent:.1}%)", lang_name(lang));
+    let locale = SystemLocale::default().unwrap();
+    #[cfg(windows)]
+    println!("{}", "-".repeat(row_width));
+    #[cfg(unix)]
+    println!("{}", "─".repeat(row_width));
+    let s = if count == 1 { ' ' } else { 's' };
+    let count = count.to_formatted_string(&locale);
+    let numbers = format!("{count} file{s}");
+    let columns = format_columns(&subtotal, &locale);
+    let width = row_width
+        .saturating_sub(columns.chars().count() + numbers.len() + 1);
+    println!("{name:<width$} {numbers}{columns}");
+}
+
+// The rule above a table's totals
+pub fn rule(row_width: usize) {
+    #[cfg(windows)]
+    println!("{}", "=".repeat(row_width));
+    #[cfg(unix)]
+    println!("{}", "━".repeat(row_width));
+}
+
+// The width of a table's first column, which has the names and "Total"
+pub fn name_width<'a>(names: impl Iterator<Item = &'a str>) -> usize {
+    names
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or_default()
+        .max("Total".len())
+}
+
+// Returns the name to report a language's row by, or None for files
+// without a language which, as for the summary, are only totalled
+pub fn row_name(lang: &str) -> Option<&'static str> {
+    consts::DATA_FOR_LANG
+        .get()
+        .get(lang)
+        .map(|lang_data| lang_data.name.as_str())
+}
+
+// Callers add a newline or a percentage
+pub fn display_total(total: &Total, row_width: usize) {
+    let locale = SystemLocale::default().unwrap();
+    let s = if total.files == 1 { ' ' } else { 's' };
+    let count = total.files.to_formatted_string(&locale);
+    let numbers = format!("{count} file{s}");
+    let columns = format_columns(&total.counts(), &locale);
+    let width = row_width
+        .saturating_sub(columns.chars().count() + numbers.len() + 1);
+    print!("{:<width$} {numbers}{columns}", "Total");
+}
+
+pub fn display_skipped(skipped: &mut [Skipped]) {
+    skipped.sort_by(|a, b| a.filename.cmp(&b.filename));
+    let s = if skipped.len() == 1 { "" } else { "s" };
+    eprintln!("skipped {} file{s}:", skipped.len());
+    for skip in skipped {
+        eprintln!("  {}: {}", skip.filename, skip.reason);
     }
 }
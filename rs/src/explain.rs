@@ -0,0 +1,261 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use crate::config::Config;
+use crate::consts;
+use crate::lang;
+use crate::util;
+use crate::valid::{self, Verdict};
+use crate::walk;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// For each path reports every rule that applies to it and then the
+// decision, which uses the same rules in the same order as counting does
+pub fn display_explanations(names: &[String], config: &Config) {
+    for (i, name) in names.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{name}");
+        let path = util::abspath(name);
+        if path.is_dir() {
+            explain_dir(&path, config);
+        } else if path.is_file() {
+            explain_file(&path, config);
+        } else {
+            println!("    decision: not counted: not found");
+        }
+    }
+}
+
+fn explain_dir(dirname: &Path, config: &Config) {
+    let Some(root) = root_for(dirname, config) else {
+        println!("    root: none (not in any folder being searched)");
+        println!("    decision: not searched");
+        return;
+    };
+    println!("    root: {}", root.display());
+    let blocked = explain_folders(dirname, &root, config);
+    let decision = match blocked {
+        Some(reason) => format!("not searched: {reason}"),
+        None => match valid::dir_verdict(dirname, &root, config) {
+            Verdict::Searched => "searched".to_string(),
+            verdict => format!("not searched: {verdict}"),
+        },
+    };
+    println!("    decision: {decision}");
+}
+
+fn explain_file(filename: &Path, config: &Config) {
+    // Files given explicitly are matched relative to their own folder and
+    // aren't subject to folder rules or ignore files
+    let explicit = is_explicit(filename, config);
+    let root = if explicit { None } else { root_for(filename, config) };
+    let root = match root {
+        Some(root) => {
+            println!("    root: {}", root.display());
+            root
+        }
+        None => {
+            let root = filename.parent().unwrap_or(filename).to_path_buf();
+            println!(
+                "    root: {} ({})",
+                root.display(),
+                if explicit {
+                    "given explicitly"
+                } else {
+                    "not in any folder being searched"
+                }
+            );
+            root
+        }
+    };
+    let relpath = filename.strip_prefix(&root).unwrap_or(filename);
+    let name = filename
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    println!(
+        "    include: {}",
+        valid::include_match(&name, relpath, config)
+            .unwrap_or_else(|| "no match".to_string())
+    );
+    let hidden = if name.starts_with('.')
+        && lang::langs_for_whole_name(&name).is_empty()
+    {
+        Some(name.clone())
+    } else {
+        relpath.parent().and_then(valid::hidden_part)
+    };
+    println!("    hidden: {}", hidden.unwrap_or_else(|| "no".to_string()));
+    let exclude = config
+        .exclude_patterns
+        .matching(relpath)
+        .map(|pattern| pattern.to_string())
+        .or_else(|| valid::excluded_part(relpath, config));
+    println!(
+        "    exclude: {}",
+        exclude.unwrap_or_else(|| "no match".to_string())
+    );
+    let mut blocked = None;
+    if !explicit {
+        blocked = explain_folders(filename, &root, config);
+        let ignored = explain_ignore_files(filename, &root, config);
+        blocked = blocked.or(ignored);
+    }
+    explain_langs(filename, config);
+    let bytes = fs::read(filename);
+    match &bytes {
+        Ok(bytes) => explain_shebang(bytes),
+        Err(err) => println!("    shebang: unreadable: {err}"),
+    }
+    let decision = if let Some(reason) = blocked {
+        format!("not counted: {reason}")
+    } else {
+        let verdict = valid::file_verdict(filename, &root, config);
+        if !verdict.is_valid() {
+            format!("not counted: {verdict}")
+        } else {
+            match &bytes {
+                Ok(bytes) => {
+                    let lang =
+                        lang::lang_for_file(filename, bytes, &config.langs);
+                    match lang::lang_data_for(&lang) {
+                        Some(lang_data) => format!(
+                            "counted as {lang} ({}): {verdict}",
+                            lang_data.name
+                        ),
+                        None => format!(
+                            "counted without a language (all nonblank \
+                             lines are code): {verdict}"
+                        ),
+                    }
+                }
+                Err(err) => format!("skipped: {err}"),
+            }
+        }
+    };
+    println!("    decision: {decision}");
+}
+
+// Reports the first folder between the root and the path (exclusive) that
+// wouldn't be searched
+fn explain_folders(
+    path: &Path,
+    root: &Path,
+    config: &Config,
+) -> Option<String> {
+    let mut folders: Vec<&Path> = path
+        .ancestors()
+        .skip(1)
+        .take_while(|folder| *folder != root && folder.starts_with(root))
+        .collect();
+    folders.reverse();
+    for folder in folders {
+        let verdict = valid::dir_verdict(folder, root, config);
+        if !verdict.is_valid() {
+            let reason = format!("folder {}: {verdict}", folder.display());
+            println!("    folders: {reason}");
+            return Some(reason);
+        }
+    }
+    println!("    folders: all searched");
+    None
+}
+
+// Walks from the root down to the path, so the ignore files in every
+// folder on the way are applied exactly as when counting
+fn explain_ignore_files(
+    filename: &Path,
+    root: &Path,
+    config: &Config,
+) -> Option<String> {
+    if config.no_ignore {
+        println!("    ignore files: not used (--no-ignore)");
+        return None;
+    }
+    let target = filename.to_path_buf();
+    let mut deepest = root.to_path_buf();
+    for entry in walk::get_walker(root, config)
+        .filter_entry(move |e| target.starts_with(e.path()))
+        .build()
+        .flatten()
+    {
+        if entry.path() == filename {
+            println!("    ignore files: not ignored");
+            return None;
+        }
+        if entry.path().starts_with(&deepest) {
+            deepest = entry.into_path();
+        }
+    }
+    // The first path component below the deepest folder reached is the
+    // one that was ignored
+    let ignored = filename
+        .strip_prefix(&deepest)
+        .ok()
+        .and_then(|rest| rest.components().next())
+        .map(|part| deepest.join(part))
+        .unwrap_or_else(|| filename.to_path_buf());
+    let reason = format!(
+        "ignored by .gitignore, .ignore, {}, or git's excludes ({})",
+        consts::IGNORE_FILENAME,
+        ignored.display()
+    );
+    println!("    ignore files: {reason}");
+    Some(reason)
+}
+
+fn explain_langs(filename: &Path, config: &Config) {
+    let langs = lang::langs_for_name(filename);
+    if langs.is_empty() {
+        println!("    name and extension: no language matches");
+    } else {
+        let langs: Vec<String> = langs
+            .iter()
+            .map(|lang| {
+                if config.langs.contains(*lang) {
+                    lang.to_string()
+                } else {
+                    format!("{lang} (not counted)")
+                }
+            })
+            .collect();
+        println!("    name and extension: {}", langs.join(", "));
+    }
+}
+
+fn explain_shebang(bytes: &[u8]) {
+    let prefix = &bytes[..bytes.len().min(consts::SHEBANG_PREFIX_SIZE)];
+    let line = prefix.split(|&b| b == b'\n').next().unwrap_or_default();
+    let line = String::from_utf8_lossy(line);
+    match util::interpreter_for_shebang(&line) {
+        None => println!("    shebang: none"),
+        Some(interpreter) => {
+            let lang = lang::lang_for_shebang(bytes);
+            if lang.is_empty() {
+                println!("    shebang: {interpreter} (no language)");
+            } else {
+                println!("    shebang: {interpreter} ({lang})");
+            }
+        }
+    }
+}
+
+fn is_explicit(filename: &Path, config: &Config) -> bool {
+    config.files.iter().any(|name| util::abspath(name) == filename)
+}
+
+// Returns the deepest folder being searched that contains the path
+fn root_for(path: &Path, config: &Config) -> Option<PathBuf> {
+    let roots: Vec<PathBuf> = config
+        .files
+        .iter()
+        .map(|name| util::abspath(name))
+        .filter(|root| root.is_dir())
+        .collect();
+    util::root_for(path, &roots).map(|root| root.to_path_buf())
+}
@@ -0,0 +1,287 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use crate::cli::Cli;
+use crate::config::Config;
+use crate::consts;
+use crate::types::{FileData, Skipped};
+use crate::util;
+use crate::valid;
+use crate::walk;
+use clap::{error, CommandFactory};
+use git2::{
+    ObjectType, Oid, Repository, Tree, TreeWalkMode, TreeWalkResult,
+};
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+use rayon::{iter::Either, prelude::*};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+const SYMLINK_MODE: i32 = 0o120000;
+
+// A blob to count, the absolute filename it would have if checked out, the
+// name to report it by, and the index of the file or folder given that it
+// was found in
+type Found = (Oid, PathBuf, String, usize);
+
+// Counts the files as they were at the given revision by reading their
+// blobs from the repository's object database. The files and folders given
+// are interpreted relative to the current folder as usual, but needn't
+// exist now. The revision's own ignore files apply as well as the usual
+// rules.
+pub fn count_rev(
+    rev: &str,
+    config: &Config,
+) -> (Vec<FileData>, Vec<Skipped>) {
+    let roots: Vec<PathBuf> =
+        config.files.iter().map(|name| absolute(name)).collect();
+    let repo = open_repo(&roots[0]);
+    let tree = tree_for_rev(&repo, rev);
+    let (found, mut skipped) = get_blobs(&repo, &tree, &roots, config);
+    let repo_path = repo.path().to_path_buf();
+    // Repositories can't be shared between threads so each has its own
+    let (file_data, unread): (Vec<_>, Vec<_>) = found
+        .par_iter()
+        .map_init(
+            || Repository::open(&repo_path),
+            |repo, (oid, filename, name, root)| match repo {
+                Ok(repo) => repo
+                    .find_blob(*oid)
+                    .map(|blob| {
+                        walk::count_bytes(
+                            filename,
+                            name,
+                            *root,
+                            blob.content(),
+                            config,
+                        )
+                    })
+                    .map_err(|err| {
+                        Skipped::new(Path::new(name), err.message())
+                    }),
+                Err(err) => {
+                    Err(Skipped::new(Path::new(name), err.message()))
+                }
+            },
+        )
+        .partition_map(|result| match result {
+            Ok(file_datum) => Either::Left(file_datum),
+            Err(skip) => Either::Right(skip),
+        });
+    skipped.extend(unread);
+    (file_data, skipped)
+}
+
+fn get_blobs(
+    repo: &Repository,
+    tree: &Tree,
+    roots: &[PathBuf],
+    config: &Config,
+) -> (Vec<Found>, Vec<Skipped>) {
+    let workdir = workdir(repo);
+    let mut ignores = RevIgnores::new(repo, tree, config);
+    let mut found = vec![];
+    let mut skipped = vec![];
+    let mut seen = HashSet::new();
+    for (index, root) in roots.iter().enumerate() {
+        let Ok(prefix) = root.strip_prefix(&workdir) else {
+            skipped.push(Skipped::new(root, "not in the repository"));
+            continue;
+        };
+        let subtree = if prefix.as_os_str().is_empty() {
+            tree.clone()
+        } else {
+            match tree.get_path(prefix) {
+                Ok(entry) if entry.kind() == Some(ObjectType::Tree) => {
+                    match entry
+                        .to_object(repo)
+                        .and_then(|o| o.peel_to_tree())
+                    {
+                        Ok(subtree) => subtree,
+                        Err(err) => {
+                            skipped.push(Skipped::new(root, err.message()));
+                            continue;
+                        }
+                    }
+                }
+                Ok(entry) if entry.kind() == Some(ObjectType::Blob) => {
+                    let folder = root.parent().unwrap_or(root);
+                    if seen.insert(root.clone())
+                        && valid::file_verdict(root, folder, config)
+                            .is_valid()
+                    {
+                        let name = walk::report_name(
+                            root, folder, index, true, config,
+                        );
+                        found.push((entry.id(), root.clone(), name, index));
+                    }
+                    continue;
+                }
+                _ => {
+                    skipped.push(Skipped::new(root, "not found"));
+                    continue;
+                }
+            }
+        };
+        let result = subtree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+            let Some(name) = entry.name() else {
+                return TreeWalkResult::Ok;
+            };
+            let filename = root.join(dir).join(name);
+            match entry.kind() {
+                Some(ObjectType::Tree) => {
+                    if valid::is_valid_dir(&filename, root, config)
+                        && !ignores.is_ignored(&filename, true)
+                    {
+                        TreeWalkResult::Ok
+                    } else {
+                        TreeWalkResult::Skip
+                    }
+                }
+                // Symlinks are stored as blobs of their targets' names
+                Some(ObjectType::Blob)
+                    if entry.filemode() != SYMLINK_MODE
+                        && !seen.contains(&filename)
+                        && valid::file_verdict(&filename, root, config)
+                            .is_valid()
+                        && !ignores.is_ignored(&filename, false) =>
+                {
+                    let name = walk::report_name(
+                        &filename, root, index, false, config,
+                    );
+                    seen.insert(filename.clone());
+                    found.push((entry.id(), filename, name, index));
+                    TreeWalkResult::Ok
+                }
+                _ => TreeWalkResult::Ok, // e.g., submodules
+            }
+        });
+        if let Err(err) = result {
+            skipped.push(Skipped::new(root, err.message()));
+        }
+    }
+    (found, skipped)
+}
+
+// The .clcignore and .ignore files in a revision's tree, read as they're
+// needed. (.gitignore files don't matter since a revision's files are all
+// tracked.)
+pub struct RevIgnores<'a> {
+    repo: &'a Repository,
+    tree: &'a Tree<'a>,
+    workdir: PathBuf,
+    no_ignore: bool,
+    ignore_for_file: HashMap<PathBuf, Option<Gitignore>>,
+}
+
+impl<'a> RevIgnores<'a> {
+    pub fn new(
+        repo: &'a Repository,
+        tree: &'a Tree<'a>,
+        config: &Config,
+    ) -> Self {
+        Self {
+            repo,
+            tree,
+            workdir: workdir(repo),
+            no_ignore: config.no_ignore,
+            ignore_for_file: HashMap::new(),
+        }
+    }
+
+    // As when walking folders, .clcignore files take precedence over
+    // .ignore files, and for each the nearest folder's file takes
+    // precedence over those above it, up to the top of the repository
+    pub fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        if self.no_ignore {
+            return false;
+        }
+        let workdir = self.workdir.clone();
+        for name in [consts::IGNORE_FILENAME, ".ignore"] {
+            for folder in path
+                .ancestors()
+                .skip(1)
+                .take_while(|folder| folder.starts_with(&workdir))
+            {
+                if let Some(gitignore) = self.ignore_for(&folder.join(name))
+                {
+                    match gitignore.matched(path, is_dir) {
+                        Match::None => {}
+                        matched => return matched.is_ignore(),
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    fn ignore_for(&mut self, filename: &Path) -> Option<&Gitignore> {
+        if !self.ignore_for_file.contains_key(filename) {
+            let gitignore = self.read_ignore(filename);
+            self.ignore_for_file.insert(filename.to_path_buf(), gitignore);
+        }
+        self.ignore_for_file[filename].as_ref()
+    }
+
+    // Invalid lines are skipped as they are when walking folders
+    fn read_ignore(&self, filename: &Path) -> Option<Gitignore> {
+        let relpath = filename.strip_prefix(&self.workdir).ok()?;
+        let entry = self.tree.get_path(relpath).ok()?;
+        let blob = self.repo.find_blob(entry.id()).ok()?;
+        let mut builder =
+            GitignoreBuilder::new(filename.parent().unwrap_or(filename));
+        for line in String::from_utf8_lossy(blob.content()).lines() {
+            let _ = builder.add_line(Some(filename.to_path_buf()), line);
+        }
+        builder.build().ok()
+    }
+}
+
+// The repository containing the first file or folder given (or the nearest
+// folder above it that exists now)
+pub fn open_repo(root: &Path) -> Repository {
+    let folder = root.ancestors().find(|folder| folder.is_dir());
+    let repo = Repository::discover(folder.unwrap_or(Path::new(".")))
+        .unwrap_or_else(|err| git_error(err.message()));
+    if repo.is_bare() {
+        git_error("bare repositories aren't supported");
+    }
+    repo
+}
+
+pub fn tree_for_rev<'a>(repo: &'a Repository, rev: &str) -> Tree<'a> {
+    repo.revparse_single(rev)
+        .and_then(|object| object.peel_to_tree())
+        .unwrap_or_else(|err| {
+            git_error(&format!(
+                "invalid revision {rev:?}: {}",
+                err.message()
+            ))
+        })
+}
+
+pub fn workdir(repo: &Repository) -> PathBuf {
+    let workdir = repo.workdir().unwrap_or(Path::new("."));
+    workdir.canonicalize().unwrap_or_else(|_| workdir.to_path_buf())
+}
+
+// Files and folders from old revisions may not exist now so can't always
+// be canonicalized
+pub fn absolute(name: &str) -> PathBuf {
+    let path = util::abspath(name);
+    if path.is_absolute() {
+        path
+    } else {
+        std::path::absolute(&path).unwrap_or(path)
+    }
+}
+
+fn git_error(message: &str) -> ! {
+    let mut cmd = Cli::command();
+    cmd.error(error::ErrorKind::Io, message).exit()
+}
@@ -0,0 +1,88 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use crate::consts;
+use regex::bytes::Regex;
+
+// A heuristic says that a file is in lang if its content matches the regex.
+// It is only tried if lang is one of the file's candidate languages.
+#[derive(Debug)]
+pub struct Heuristic {
+    pub lang: &'static str,
+    pub regex: Regex,
+}
+
+// The order matters: e.g., Objective-C headers often use C++-like syntax
+// so are tested first, and Perl is tested before Prolog since Perl code
+// may contain `:-`.
+pub fn initial_heuristics() -> Vec<Heuristic> {
+    [
+        (
+            "objc",
+            r"(?m)^\s*(@interface|@implementation|@protocol|@end\b|#import\s)",
+        ),
+        (
+            "cpp",
+            concat!(
+                r"(?m)^\s*(class\s+\w+\s*[:{;]|namespace\s*\w*\s*\{|",
+                r"template\s*<|using\s+namespace\s|",
+                r"(public|private|protected)\s*:)|std::|",
+                r"#include\s*<(iostream|string|vector|map|memory|algorithm|",
+                r"cstdint|cstdio|cstdlib)>",
+            ),
+        ),
+        ("pl", r"(?m)^\s*(use\s+(strict|warnings)\b|my\s+[$@%]|sub\s+\w+)"),
+        ("prolog", r"(?m)^\s*:-|^[a-z]\w*(\(.*\))?\s*:-"),
+        ("matlab", r"(?m)^\s*(function\b|%|end\s*;?\s*$)"),
+    ]
+    .into_iter()
+    .map(|(lang, pattern)| Heuristic {
+        lang,
+        regex: Regex::new(pattern).expect("valid heuristic regex"),
+    })
+    .collect()
+}
+
+// Returns the first candidate whose heuristic matches the start of the
+// text, or None if there isn't one
+pub fn disambiguate<'a>(
+    candidates: &[&'a str],
+    bytes: &[u8],
+) -> Option<&'a str> {
+    let bytes = &bytes[..bytes.len().min(consts::HEURISTIC_PREFIX_SIZE)];
+    for heuristic in consts::HEURISTICS.get() {
+        if let Some(lang) =
+            candidates.iter().find(|lang| **lang == heuristic.lang)
+        {
+            if heuristic.regex.is_match(bytes) {
+                return Some(lang);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_languages() {
+        consts::initialize();
+        let candidates = ["c", "cpp", "objc"];
+        let lang = |text: &str| disambiguate(&candidates, text.as_bytes());
+        assert_eq!(lang("#include <vector>\nint x;\n"), Some("cpp"));
+        assert_eq!(lang("namespace app {\n}\n"), Some("cpp"));
+        assert_eq!(lang("@interface Foo : NSObject\n@end\n"), Some("objc"));
+        assert_eq!(lang("#include <stdio.h>\nint x;\n"), None);
+    }
+
+    #[test]
+    fn only_candidates_are_tried() {
+        consts::initialize();
+        let text = b"use strict;\nfoo :- bar.\n";
+        assert_eq!(disambiguate(&["pl", "prolog"], text), Some("pl"));
+        assert_eq!(disambiguate(&["prolog"], text), Some("prolog"));
+        assert_eq!(disambiguate(&["c"], b"class A {};\n"), None);
+    }
+}
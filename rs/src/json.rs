@@ -0,0 +1,18 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use crate::config::Config;
+use crate::report::Report;
+use crate::types::FileData;
+use std::io::{self, Write};
+
+pub fn display(file_data: Vec<FileData>, config: Config) {
+    let report = Report::new(file_data, &config);
+    let mut out = io::stdout().lock();
+    if let Err(err) = serde_json::to_writer_pretty(&mut out, &report)
+        .map_err(io::Error::from)
+        .and_then(|_| writeln!(out))
+    {
+        eprintln!("failed to write JSON: {err}");
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use crate::consts;
+use crate::heuristics;
+use crate::types::LangData;
+use crate::util;
+use std::{collections::HashSet, path::Path};
+
+// Languages claimed by name are preferred to those claimed by extension.
+// If several remain, the file's content decides, and failing that the
+// alphabetically first lang, so the result never depends on hash order.
+pub fn lang_for_file(
+    filename: &Path,
+    bytes: &[u8],
+    langs: &HashSet<String>,
+) -> String {
+    let mut candidates = langs_for_name(filename);
+    // Files given with --include may not be in any of the langs
+    if candidates.iter().any(|lang| langs.contains(*lang)) {
+        candidates.retain(|lang| langs.contains(*lang));
+    }
+    match candidates.len() {
+        0 => lang_for_shebang(bytes),
+        1 => candidates[0].to_string(),
+        _ => heuristics::disambiguate(&candidates, bytes)
+            .unwrap_or(candidates[0])
+            .to_string(),
+    }
+}
+
+// Returns every language that claims the file: those matching its whole
+// name (e.g., Makefile) come before those matching its extension, and each
+// group is in lang order.
+pub fn langs_for_name(filename: &Path) -> Vec<&'static str> {
+    let data_for_lang = consts::DATA_FOR_LANG.get();
+    let mut by_name = filename
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(langs_for_whole_name)
+        .unwrap_or_default();
+    let mut by_ext = vec![];
+    if let Some(ext) = filename.extension().and_then(|e| e.to_str()) {
+        by_ext = data_for_lang
+            .iter()
+            .filter(|(lang, lang_data)| {
+                lang_data.exts.contains(ext)
+                    && !by_name.contains(&lang.as_str())
+            })
+            .map(|(lang, _)| lang.as_str())
+            .collect();
+        by_ext.sort_unstable();
+    }
+    by_name.extend(by_ext);
+    by_name
+}
+
+// Returns the languages (in lang order) that claim the file name itself,
+// e.g., Makefile or .bashrc
+pub fn langs_for_whole_name(name: &str) -> Vec<&'static str> {
+    let mut langs: Vec<&str> = consts::DATA_FOR_LANG
+        .get()
+        .iter()
+        .filter(|(_, lang_data)| lang_data.matches_filename(name))
+        .map(|(lang, _)| lang.as_str())
+        .collect();
+    langs.sort_unstable();
+    langs
+}
+
+// Only the first line matters so we only look at a bounded prefix
+pub fn lang_for_shebang(bytes: &[u8]) -> String {
+    let prefix = &bytes[..bytes.len().min(consts::SHEBANG_PREFIX_SIZE)];
+    if prefix.starts_with(b"#!") {
+        let line = prefix.split(|&b| b == b'\n').next().unwrap_or_default();
+        lang_for_line(&String::from_utf8_lossy(line))
+    } else {
+        String::new()
+    }
+}
+
+fn lang_for_line(line: &str) -> String {
+    if let Some(interpreter) = util::interpreter_for_shebang(line) {
+        let data_for_lang = consts::DATA_FOR_LANG.get();
+        // Try the exact name first, e.g., python3.11, then the unversioned
+        // name, e.g., python
+        for name in [interpreter.as_str(), util::unversioned(&interpreter)]
+        {
+            if let Some(lang) = data_for_lang
+                .iter()
+                .filter(|(_, lang_data)| {
+                    lang_data.interpreters.contains(name)
+                })
+                .map(|(lang, _)| lang)
+                .min()
+            {
+                return lang.to_string();
+            }
+        }
+    }
+    String::new()
+}
+
+pub fn lang_data_for(lang: &str) -> Option<&'static LangData> {
+    consts::DATA_FOR_LANG.get().get(lang)
+}
@@ -1,113 +1,232 @@
 // Copyright © 2022 Mark Summerfield. All rights reserved.
 // License: GPLv3
 
+mod baseline;
+mod bydir;
+mod byroot;
 mod cli;
+mod cloc;
 mod config;
+mod configfile;
 mod consts;
+mod count;
+mod delimited;
+mod diff;
 mod display;
+mod explain;
+mod git;
+mod heuristics;
+mod json;
+mod lang;
+mod report;
+mod show;
 mod types;
+mod unknown;
 mod util;
 mod valid;
+mod walk;
 
 use anyhow::Result;
+use cli::Format;
 use config::Config;
-use rayon::prelude::*;
+use rayon::{iter::Either, prelude::*};
 use std::{
+    collections::HashSet,
     fs::File,
-    io::Read,
     path::{Path, PathBuf},
+    process,
+    sync::Arc,
     time::Instant,
 };
-use types::FileData;
-use walkdir::WalkDir;
+use types::{FileData, Skipped};
+use valid::Verdict;
+
+// A file to count, the name to report it by, and the index of the file or
+// folder given that it was found in
+type Found = (PathBuf, String, usize);
 
 fn main() {
     consts::initialize(); // NOTE must be first
     let config = Config::new();
+    if config.list_languages {
+        show::display_languages();
+        return;
+    }
+    if config.show_config {
+        show::display_config(&config);
+        return;
+    }
+    if !config.explain.is_empty() {
+        explain::display_explanations(&config.explain, &config);
+        return;
+    }
+    if let Some((rev1, rev2)) = &config.diff {
+        let mut skipped = diff::display(rev1, rev2, &config);
+        report_skipped(&mut skipped, config.strict);
+        return;
+    }
     let t = Instant::now();
-    let filenames = get_filenames(&config);
-    let file_data = filenames
-        .par_iter()
-        .filter_map(|filename| process_one(filename).ok())
-        .collect();
-    if config.summary {
-        display::display_summary(file_data, config, t);
+    // Read first so that a bad baseline is reported before counting
+    let baseline = config.baseline.as_deref().map(baseline::read_baseline);
+    let (file_data, mut skipped) = if let Some(rev) = &config.git_rev {
+        git::count_rev(rev, &config)
     } else {
-        display::display_full(file_data, config);
+        let (filenames, unknown, mut skipped) = get_filenames(&config);
+        if let Some(n) = config.unknown {
+            unknown::display_unknown(&unknown, n);
+            report_skipped(&mut skipped, config.strict);
+            return;
+        }
+        let (file_data, unread): (Vec<_>, Vec<_>) = filenames
+            .par_iter()
+            .map(|(filename, name, root)| {
+                process_one(filename, name, *root, &config).map_err(|err| {
+                    Skipped::new(Path::new(name), &err.to_string())
+                })
+            })
+            .partition_map(|result| match result {
+                Ok(file_datum) => Either::Left(file_datum),
+                Err(skip) => Either::Right(skip),
+            });
+        skipped.extend(unread);
+        (file_data, skipped)
+    };
+    let strict = config.strict;
+    match config.format {
+        Format::Csv => delimited::display(file_data, config, ','),
+        Format::Json => json::display(file_data, config),
+        Format::Tsv => delimited::display(file_data, config, '\t'),
+        Format::Xml => cloc::display_xml(file_data, config, t),
+        Format::Yaml => cloc::display_yaml(file_data, config, t),
+        Format::Table => {
+            if let Some(baseline) = baseline {
+                baseline::display(file_data, config, baseline);
+            } else if let Some(depth) = config.by_dir {
+                bydir::display(file_data, config, depth);
+            } else if config.by_root {
+                byroot::display(file_data, config);
+            } else if config.summary {
+                display::display_summary(file_data, config, t);
+            } else {
+                display::display_full(file_data, config);
+            }
+        }
     }
+    report_skipped(&mut skipped, strict);
 }
 
-fn process_one(filename: &Path) -> Result<FileData> {
-    let mut file = File::open(&filename)?;
-    let (count, lang) = if let Some(lang) = lang_for_name(filename) {
-        let mmap = unsafe { memmap2::Mmap::map(&file)? };
-        let count = mmap.iter().filter(|&b| *b == b'\n').count();
-        (count, lang)
-    } else {
-        let mut text = String::new();
-        file.read_to_string(&mut text)?;
-        let count = text.bytes().filter(|&b| b == b'\n').count();
-        let lang = if text.starts_with("#!") {
-            if let Some(i) = text.find('\n') {
-                lang_for_line(&text[..i])
-            } else {
-                ""
-            }
-        } else {
-            ""
-        };
-        (count, lang.to_string())
-    };
-    Ok(FileData::new(lang, filename, count))
+fn report_skipped(skipped: &mut [Skipped], strict: bool) {
+    if !skipped.is_empty() {
+        display::display_skipped(skipped);
+        if strict {
+            process::exit(1);
+        }
+    }
+}
+
+fn process_one(
+    filename: &Path,
+    name: &str,
+    root: usize,
+    config: &Config,
+) -> Result<FileData> {
+    let file = File::open(filename)?;
+    let mmap = unsafe { memmap2::Mmap::map(&file)? };
+    Ok(walk::count_bytes(filename, name, root, &mmap, config))
 }
 
-fn get_filenames(config: &Config) -> Vec<PathBuf> {
+// Returns the files to count (with the names to report them by and the
+// index of the file or folder they were found in), the files that no
+// language recognizes (only if they're wanted for --unknown), and those
+// that couldn't be searched. Files found more than once (e.g., in a folder
+// and one of its subfolders) are only counted for the first.
+fn get_filenames(
+    config: &Config,
+) -> (Vec<Found>, Vec<PathBuf>, Vec<Skipped>) {
     let mut filenames = Vec::with_capacity(1000);
-    for name in &config.files {
+    let mut unknown = vec![];
+    let mut skipped = vec![];
+    let mut seen = HashSet::new();
+    let mut add =
+        |filename: PathBuf, root: &Path, index: usize, explicit| {
+            if !seen.insert(filename.clone()) {
+                return;
+            }
+            match valid::file_verdict(&filename, root, config) {
+                verdict if verdict.is_valid() => {
+                    let name = walk::report_name(
+                        &filename, root, index, explicit, config,
+                    );
+                    filenames.push((filename, name, index))
+                }
+                Verdict::Unknown if config.unknown.is_some() => {
+                    unknown.push(filename)
+                }
+                _ => {}
+            }
+        };
+    let shared_config = Arc::new(config.clone()); // The filter needs 'static
+    for (index, name) in config.files.iter().enumerate() {
         let filename = util::abspath(name);
         if filename.is_file() {
-            if valid::is_valid_file(&filename, config) {
-                filenames.push(filename);
-            }
+            let root = filename.parent().unwrap_or(&filename).to_path_buf();
+            add(filename, &root, index, true);
         } else if filename.is_dir() {
-            for entry in WalkDir::new(&filename)
-                .into_iter()
-                .filter_entry(|e| valid::is_valid_entry(e, config))
-                .flatten()
+            let shared_config = Arc::clone(&shared_config);
+            let root = filename.clone();
+            // Only folders are filtered here so that files are only
+            // checked once
+            for entry in walk::get_walker(&filename, config)
+                .filter_entry(move |e| {
+                    !e.file_type().is_some_and(|t| t.is_dir())
+                        || valid::is_valid_dir(
+                            e.path(),
+                            &root,
+                            &shared_config,
+                        )
+                })
+                .build()
             {
-                if !entry.file_type().is_dir() {
-                    filenames.push(entry.into_path());
+                match entry {
+                    Ok(entry) => {
+                        // A folder's entry carries any errors in its
+                        // ignore files
+                        if let Some(err) = entry.error() {
+                            report_walk_error(err, &mut skipped);
+                        }
+                        if !entry.file_type().is_some_and(|t| t.is_dir()) {
+                            add(entry.into_path(), &filename, index, false);
+                        }
+                    }
+                    Err(err) => report_walk_error(&err, &mut skipped),
                 }
             }
+        } else {
+            skipped.push(Skipped::new(&filename, "not found"));
         }
     }
-    filenames
+    (filenames, unknown, skipped)
 }
 
-fn lang_for_name(name: &Path) -> Option<String> {
-    if let Some(ext) = name.extension() {
-        let ext = ext.to_string_lossy().to_string();
-        for (lang, lang_data) in consts::DATA_FOR_LANG.get().iter() {
-            if lang_data.exts.contains(ext.as_str()) {
-                return Some(lang.to_string());
+// Files and folders that couldn't be read are skipped, but invalid lines in
+// ignore files (which have no path of their own to skip) are just warned
+// about since they don't stop anything being counted
+fn report_walk_error(err: &ignore::Error, skipped: &mut Vec<Skipped>) {
+    match err {
+        ignore::Error::Partial(errs) => {
+            for err in errs {
+                report_walk_error(err, skipped);
             }
         }
-    }
-    None
-}
-
-fn lang_for_line(line: &str) -> &str {
-    if line.contains("julia") {
-        "jl"
-    } else if line.contains("perl") {
-        "pl"
-    } else if line.contains("python") {
-        "py"
-    } else if line.contains("ruby") {
-        "rb"
-    } else if line.contains("tcl") {
-        "tcl"
-    } else {
-        ""
+        ignore::Error::WithDepth { err, .. } => {
+            report_walk_error(err, skipped)
+        }
+        ignore::Error::WithPath { path, err } if err.is_io() => {
+            skipped.push(Skipped::new(path, &err.to_string()))
+        }
+        ignore::Error::Loop { child, .. } => {
+            skipped.push(Skipped::new(child, "file system loop"))
+        }
+        err => eprintln!("warning: {err}"),
     }
 }
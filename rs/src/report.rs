@@ -0,0 +1,199 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use crate::config::Config;
+use crate::consts;
+use crate::types::{FileData, LineCounts};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::HashMap};
+
+// The data shared by every output format; the field names are part of the
+// JSON output so must be kept stable.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Report {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<FileData>>,
+    pub languages: Vec<LangTotal>,
+    pub total: Total,
+}
+
+impl Report {
+    pub fn new(mut file_data: Vec<FileData>, config: &Config) -> Self {
+        let mut languages = get_lang_totals(&file_data, config.sortbylines);
+        let total = get_total(&file_data);
+        for lang_total in &mut languages {
+            lang_total.percent = percent(lang_total.lines, total.lines);
+        }
+        let files = if config.summary {
+            None
+        } else {
+            sort_file_data(&mut file_data, config.sortbylines);
+            Some(file_data)
+        };
+        Self { files, languages, total }
+    }
+
+    pub fn name_for_lang<'a>(&'a self, lang: &'a str) -> &'a str {
+        self.languages
+            .iter()
+            .find(|lang_total| lang_total.lang == lang)
+            .map_or(lang, |lang_total| lang_total.name.as_str())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LangTotal {
+    pub lang: String,
+    pub name: String,
+    pub files: usize,
+    pub lines: usize,
+    pub code: usize,
+    pub comments: usize,
+    pub blanks: usize,
+    // Of the total lines; reports from before percentages were added don't
+    // have it
+    #[serde(default)]
+    pub percent: f64,
+}
+
+impl LangTotal {
+    fn new(lang: &str) -> Self {
+        let name = if let Some(lang_data) =
+            consts::DATA_FOR_LANG.get().get(lang)
+        {
+            lang_data.name.clone()
+        } else {
+            lang.to_string()
+        };
+        Self {
+            lang: lang.to_string(),
+            name,
+            files: 0,
+            lines: 0,
+            code: 0,
+            comments: 0,
+            blanks: 0,
+            percent: 0.0,
+        }
+    }
+
+    pub fn counts(&self) -> LineCounts {
+        LineCounts {
+            code: self.code,
+            comments: self.comments,
+            blanks: self.blanks,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Total {
+    pub files: usize,
+    pub lines: usize,
+    pub code: usize,
+    pub comments: usize,
+    pub blanks: usize,
+}
+
+impl Total {
+    pub fn counts(&self) -> LineCounts {
+        LineCounts {
+            code: self.code,
+            comments: self.comments,
+            blanks: self.blanks,
+        }
+    }
+}
+
+pub fn get_total(file_data: &[FileData]) -> Total {
+    let mut total = Total::default();
+    for file_datum in file_data {
+        total.files += 1;
+        total.lines += file_datum.lines;
+        total.code += file_datum.code;
+        total.comments += file_datum.comments;
+        total.blanks += file_datum.blanks;
+    }
+    total
+}
+
+// Rounded to two decimal places so that it is stable in reports
+pub fn percent(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        (part as f64 * 10000.0 / whole as f64).round() / 100.0
+    }
+}
+
+pub fn get_lang_totals(
+    file_data: &[FileData],
+    sortbylines: bool,
+) -> Vec<LangTotal> {
+    let mut total_for_lang = HashMap::<&str, LangTotal>::new();
+    for file_datum in file_data {
+        let lang_total = total_for_lang
+            .entry(file_datum.lang.as_str())
+            .or_insert_with(|| LangTotal::new(&file_datum.lang));
+        lang_total.files += 1;
+        lang_total.lines += file_datum.lines;
+        lang_total.code += file_datum.code;
+        lang_total.comments += file_datum.comments;
+        lang_total.blanks += file_datum.blanks;
+    }
+    let mut totals: Vec<LangTotal> = total_for_lang.into_values().collect();
+    if sortbylines {
+        totals.sort_by(|a, b| {
+            if a.lines != b.lines {
+                a.lines.partial_cmp(&b.lines).unwrap_or(Ordering::Equal)
+            } else {
+                a.lang
+                    .to_lowercase()
+                    .partial_cmp(&b.lang.to_lowercase())
+                    .unwrap_or(Ordering::Equal)
+            }
+        });
+    } else {
+        totals.sort_by(|a, b| {
+            a.lang
+                .to_lowercase()
+                .partial_cmp(&b.lang.to_lowercase())
+                .unwrap_or_else(|| {
+                    a.lines.partial_cmp(&b.lines).unwrap_or(Ordering::Equal)
+                })
+        });
+    }
+    totals
+}
+
+pub fn sort_file_data(file_data: &mut [FileData], sortbylines: bool) {
+    if sortbylines {
+        file_data.sort_by(|a, b| {
+            if a.lang != b.lang {
+                a.lang.partial_cmp(&b.lang).unwrap_or(Ordering::Equal)
+            } else if a.lines != b.lines {
+                a.lines.partial_cmp(&b.lines).unwrap_or(Ordering::Equal)
+            } else {
+                a.lang
+                    .to_lowercase()
+                    .partial_cmp(&b.lang.to_lowercase())
+                    .unwrap_or(Ordering::Equal)
+            }
+        });
+    } else {
+        file_data.sort_by(|a, b| {
+            if a.lang != b.lang {
+                a.lang.partial_cmp(&b.lang).unwrap_or(Ordering::Equal)
+            } else {
+                a.filename
+                    .to_lowercase()
+                    .partial_cmp(&b.filename.to_lowercase())
+                    .unwrap_or_else(|| {
+                        a.lines
+                            .partial_cmp(&b.lines)
+                            .unwrap_or(Ordering::Equal)
+                    })
+            }
+        });
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use crate::config::Config;
+use crate::consts;
+use clap::ValueEnum;
+
+pub fn display_languages() {
+    let data_for_lang = consts::DATA_FOR_LANG.get();
+    let mut langs: Vec<&String> = data_for_lang.keys().collect();
+    langs.sort_unstable_by_key(|lang| lang.to_lowercase());
+    let lang_width = langs.iter().map(|lang| lang.len()).max().unwrap_or(4);
+    let name_width = data_for_lang
+        .values()
+        .map(|lang_data| lang_data.name.chars().count())
+        .max()
+        .unwrap_or(4);
+    let indent = lang_width + name_width + 2;
+    println!(
+        "{:lang_width$} {:name_width$} extensions and file names",
+        "lang", "name"
+    );
+    for lang in langs {
+        let lang_data = &data_for_lang[lang];
+        let mut exts: Vec<String> =
+            lang_data.exts.iter().map(|ext| format!(".{ext}")).collect();
+        exts.sort_unstable();
+        let mut filenames: Vec<&String> =
+            lang_data.filenames.iter().collect();
+        filenames.sort_unstable();
+        exts.extend(filenames.into_iter().cloned());
+        exts.extend(lang_data.filename_globs.iter().cloned());
+        println!(
+            "{lang:lang_width$} {:name_width$} {}",
+            lang_data.name,
+            exts.join(" ")
+        );
+        if lang_data.origin != "built-in" {
+            println!("{:indent$}defined in {}", "", lang_data.origin);
+        }
+        for update in &lang_data.overrides {
+            println!("{:indent$}updated by {update}", "");
+        }
+    }
+}
+
+pub fn display_config(config: &Config) {
+    println!("config files read:");
+    if config.config_files.is_empty() {
+        println!("    (none)");
+    }
+    for filename in &config.config_files {
+        println!("    {}", filename.display());
+    }
+    display_list("excluded names", &config.exclude);
+    display_list("excluded globs", &config.exclude_patterns.globs);
+    display_list("excluded regexes", config.exclude_patterns.regexes());
+    display_list("included names", &config.include);
+    display_list("included globs", &config.include_patterns.globs);
+    display_list("included regexes", config.include_patterns.regexes());
+    println!(
+        "hidden files and folders: excluded unless included{}",
+        if config.no_ignore {
+            "\nignore files: not used (--no-ignore)".to_string()
+        } else {
+            format!(
+                "\nignore files: .gitignore .ignore {} \
+                 .git/info/exclude core.excludesFile",
+                consts::IGNORE_FILENAME
+            )
+        }
+    );
+    let all = consts::DATA_FOR_LANG.get().len();
+    if config.langs.len() == all {
+        println!("languages counted: all {all}");
+    } else {
+        display_list("languages counted", &config.langs);
+    }
+    println!(
+        "sort: {}",
+        if config.sortbylines { "lines" } else { "names" }
+    );
+    if let Some(format) = config.format.to_possible_value() {
+        println!("format: {}", format.get_name());
+    }
+    println!("summary: {}", if config.summary { "yes" } else { "no" });
+    println!();
+    display_languages();
+}
+
+fn display_list<'a>(
+    title: &str,
+    items: impl IntoIterator<Item = &'a String>,
+) {
+    let mut items: Vec<&String> = items.into_iter().collect();
+    items.sort_unstable_by_key(|item| item.to_lowercase());
+    let items: Vec<&str> = items.iter().map(|item| item.as_str()).collect();
+    if items.is_empty() {
+        println!("{title}: (none)");
+    } else {
+        println!("{title}: {}", items.join(" "));
+    }
+}
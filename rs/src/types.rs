@@ -1,35 +1,204 @@
 // Copyright © 2022 Mark Summerfield. All rights reserved.
 // License: GPLv3
 
-use std::{collections::HashSet, path::Path};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct FileData {
     pub lang: String,
-    pub filename: String,
+    pub filename: String, // As reported, e.g., relative to its root
+    #[serde(skip)]
+    pub path: PathBuf, // Absolute
+    #[serde(skip)]
+    pub root: usize, // Index of the file or folder given it was found in
     pub lines: usize,
+    pub code: usize,
+    pub comments: usize,
+    pub blanks: usize,
 }
 
 impl FileData {
-    pub fn new(lang: String, filename: &Path, lines: usize) -> Self {
+    pub fn new(
+        lang: String,
+        path: &Path,
+        filename: &str,
+        root: usize,
+        counts: LineCounts,
+    ) -> Self {
         Self {
             lang,
+            filename: filename.to_string(),
+            path: path.to_path_buf(),
+            root,
+            lines: counts.lines(),
+            code: counts.code,
+            comments: counts.comments,
+            blanks: counts.blanks,
+        }
+    }
+
+    pub fn counts(&self) -> LineCounts {
+        LineCounts {
+            code: self.code,
+            comments: self.comments,
+            blanks: self.blanks,
+        }
+    }
+}
+
+// A file that couldn't be read (or a folder that couldn't be searched)
+#[derive(Debug)]
+pub struct Skipped {
+    pub filename: String,
+    pub reason: String,
+}
+
+impl Skipped {
+    pub fn new(filename: &Path, reason: &str) -> Self {
+        Self {
             filename: filename.to_string_lossy().to_string(),
-            lines,
+            reason: reason.to_string(),
         }
     }
 }
 
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LineCounts {
+    pub code: usize,
+    pub comments: usize,
+    pub blanks: usize,
+}
+
+impl LineCounts {
+    pub fn lines(&self) -> usize {
+        self.code + self.comments + self.blanks
+    }
+
+    pub fn add(&mut self, other: &LineCounts) {
+        self.code += other.code;
+        self.comments += other.comments;
+        self.blanks += other.blanks;
+    }
+}
+
 #[derive(Debug)]
 pub struct LangData {
     pub name: String,
     pub exts: HashSet<String>,
+    pub line_comments: Vec<String>,
+    pub block_comments: Vec<(String, String)>,
+    pub nested_comments: Vec<(String, String)>,
+    pub strings: Vec<(String, String)>,
+    pub raw_strings: Vec<(String, String)>,
+    pub multiline_strings: Vec<String>, // One char starts that span lines
+    pub char_literals: bool,
+    pub interpreters: HashSet<String>, // For shebang lines
+    pub filenames: HashSet<String>,    // Exact names, e.g., Makefile
+    pub filename_globs: Vec<String>,   // e.g., Dockerfile.*
+    filename_globset: GlobSet,
+    pub origin: String, // "built-in" or the file:line that defined it
+    pub overrides: Vec<String>, // The file:line (fields) of each update
 }
 
 impl LangData {
-    pub fn new(name: &str, exts: HashSet<&str>) -> Self {
+    pub fn new(
+        name: &str,
+        exts: HashSet<&str>,
+        line_comments: &[&str],
+        block_comments: &[(&str, &str)],
+    ) -> Self {
         let exts: HashSet<String> =
             exts.iter().map(|e| e.to_string()).collect();
-        Self { name: name.to_string(), exts }
+        let line_comments =
+            line_comments.iter().map(|c| c.to_string()).collect();
+        Self {
+            name: name.to_string(),
+            exts,
+            line_comments,
+            block_comments: to_pairs(block_comments),
+            nested_comments: vec![],
+            strings: to_pairs(&[("\"", "\"")]),
+            raw_strings: vec![],
+            multiline_strings: vec![],
+            char_literals: false,
+            interpreters: HashSet::new(),
+            filenames: HashSet::new(),
+            filename_globs: vec![],
+            filename_globset: GlobSet::empty(),
+            origin: "built-in".to_string(),
+            overrides: vec![],
+        }
+    }
+
+    pub fn with_nested_comments(mut self, delims: &[(&str, &str)]) -> Self {
+        self.nested_comments = to_pairs(delims);
+        self
+    }
+
+    // Strings in which a backslash escapes the following character
+    pub fn with_strings(mut self, delims: &[(&str, &str)]) -> Self {
+        self.strings = to_pairs(delims);
+        self
     }
+
+    pub fn with_raw_strings(mut self, delims: &[(&str, &str)]) -> Self {
+        self.raw_strings = to_pairs(delims);
+        self
+    }
+
+    // Multi-char starts and backticks always span lines
+    pub fn with_multiline_strings(mut self, starts: &[&str]) -> Self {
+        self.multiline_strings =
+            starts.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    pub fn with_char_literals(mut self) -> Self {
+        self.char_literals = true;
+        self
+    }
+
+    pub fn with_interpreters(mut self, interpreters: &[&str]) -> Self {
+        self.interpreters =
+            interpreters.iter().map(|i| i.to_string()).collect();
+        self
+    }
+
+    // Names with glob characters are patterns (e.g., Dockerfile.*) unless
+    // they're invalid in which case they're taken literally
+    pub fn with_filenames(mut self, names: &[&str]) -> Self {
+        self.filenames.clear();
+        self.filename_globs.clear();
+        let mut builder = GlobSetBuilder::new();
+        for name in names {
+            if name.contains(['*', '?', '[', '{']) {
+                if let Ok(glob) = Glob::new(name) {
+                    builder.add(glob);
+                    self.filename_globs.push(name.to_string());
+                    continue;
+                }
+            }
+            self.filenames.insert(name.to_string());
+        }
+        self.filename_globset =
+            builder.build().unwrap_or_else(|_| GlobSet::empty());
+        self
+    }
+
+    pub fn matches_filename(&self, name: &str) -> bool {
+        self.filenames.contains(name)
+            || self.filename_globset.is_match(name)
+    }
+}
+
+fn to_pairs(delims: &[(&str, &str)]) -> Vec<(String, String)> {
+    delims
+        .iter()
+        .map(|(start, end)| (start.to_string(), end.to_string()))
+        .collect()
 }
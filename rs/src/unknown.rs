@@ -0,0 +1,78 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use crate::consts;
+use crate::count;
+use num_format::{SystemLocale, ToFormattedString};
+use rayon::prelude::*;
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+// Lists the n most common extensions (or whole names for files without
+// one) that no language recognizes, so they can be added to clc.toml
+pub fn display_unknown(filenames: &[PathBuf], n: usize) {
+    let lines_for_file: Vec<(String, usize)> = filenames
+        .par_iter()
+        .map(|filename| {
+            let key = match filename.extension() {
+                Some(ext) => format!(".{}", ext.to_string_lossy()),
+                None => filename
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            };
+            (key, line_count(filename))
+        })
+        .collect();
+    let mut totals: HashMap<String, (usize, usize)> = HashMap::new();
+    for (key, lines) in lines_for_file {
+        let total = totals.entry(key).or_default();
+        total.0 += 1;
+        total.1 += lines;
+    }
+    if totals.is_empty() {
+        println!("no unrecognized files");
+        return;
+    }
+    let mut totals = Vec::from_iter(totals);
+    totals.sort_unstable_by(
+        |(akey, (afiles, alines)), (bkey, (bfiles, blines))| {
+            bfiles.cmp(afiles).then(blines.cmp(alines)).then(akey.cmp(bkey))
+        },
+    );
+    let locale = SystemLocale::default().unwrap();
+    let count_width = consts::FILE_COUNT_WIDTH;
+    let lines_width = consts::LINE_COUNT_WIDTH;
+    println!(
+        "{:>count_width$} {:>lines_width$} extension or file name",
+        "files", "lines"
+    );
+    let shown = n.min(totals.len());
+    for (key, (files, lines)) in &totals[..shown] {
+        println!(
+            "{:>count_width$} {:>lines_width$} {key}",
+            files.to_formatted_string(&locale),
+            lines.to_formatted_string(&locale)
+        );
+    }
+    if shown < totals.len() {
+        let rest = totals.len() - shown;
+        let s = if rest == 1 { "" } else { "s" };
+        println!("(and {rest} more extension{s} or file name{s})");
+    }
+}
+
+// Unreadable files just count as having no lines since they'd be
+// skipped anyway
+fn line_count(filename: &Path) -> usize {
+    let Ok(file) = File::open(filename) else {
+        return 0;
+    };
+    match unsafe { memmap2::Mmap::map(&file) } {
+        Ok(mmap) => count::count_lines(&mmap, None).lines(),
+        Err(_) => 0,
+    }
+}
@@ -1,7 +1,7 @@
 // Copyright © 2022 Mark Summerfield. All rights reserved.
 // License: GPLv3
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub fn abspath(name: &str) -> PathBuf {
     let filename = PathBuf::from(name);
@@ -12,6 +12,16 @@ pub fn abspath(name: &str) -> PathBuf {
     }
 }
 
+// Returns the deepest of the roots (absolute folders) that contains the
+// path, if any
+pub fn root_for<'a>(path: &Path, roots: &'a [PathBuf]) -> Option<&'a Path> {
+    roots
+        .iter()
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count())
+        .map(|root| root.as_path())
+}
+
 pub fn elide(s: &str, offset: usize, width: usize) -> String {
     assert!(offset + 5 < width);
     let chars: Vec<char> = s.chars().collect();
@@ -28,3 +38,82 @@ pub fn elide(s: &str, offset: usize, width: usize) -> String {
         format!("{left}{ellipsis}{right}")
     }
 }
+
+// Returns the interpreter's name from a shebang line, e.g., "python3" from
+// "#!/usr/bin/python3", "#!/usr/bin/env python3" or
+// "#!/usr/bin/env -S FOO=1 python3 -u"
+pub fn interpreter_for_shebang(line: &str) -> Option<String> {
+    let line = line.strip_prefix("#!")?;
+    let mut words = line.split_whitespace();
+    let mut program = basename(words.next()?);
+    if program == "env" {
+        program = "";
+        while let Some(word) = words.next() {
+            if let Some(word) = word.strip_prefix("-S") {
+                if !word.is_empty() {
+                    program = basename(word); // e.g., -Spython3
+                    break;
+                }
+            } else if word == "-u" || word == "-C" || word == "--unset" {
+                words.next(); // Skip the option's argument
+            } else if !word.starts_with('-') && !word.contains('=') {
+                program = basename(word);
+                break;
+            }
+        }
+    }
+    if program.is_empty() {
+        None
+    } else {
+        Some(program.to_string())
+    }
+}
+
+// Returns the name without any trailing version, e.g., "python" for
+// "python3.11" or "ruby" for "ruby-2.7"
+pub fn unversioned(name: &str) -> &str {
+    name.trim_end_matches(|c: char| {
+        c.is_ascii_digit() || c == '.' || c == '-'
+    })
+}
+
+fn basename(name: &str) -> &str {
+    Path::new(name).file_name().and_then(|s| s.to_str()).unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shebang_interpreters() {
+        let interpreter = |line| interpreter_for_shebang(line);
+        assert_eq!(interpreter("#!/bin/sh"), Some("sh".to_string()));
+        assert_eq!(
+            interpreter("#!/usr/bin/env python3"),
+            Some("python3".to_string())
+        );
+        assert_eq!(
+            interpreter("#!/usr/bin/env -S python3 -u"),
+            Some("python3".to_string())
+        );
+        assert_eq!(
+            interpreter("#!/usr/bin/env -Sperl -w"),
+            Some("perl".to_string())
+        );
+        assert_eq!(
+            interpreter("#!/usr/bin/env -u HOME LANG=C bash"),
+            Some("bash".to_string())
+        );
+        assert_eq!(interpreter("#!/usr/bin/env"), None);
+        assert_eq!(interpreter("# not a shebang"), None);
+    }
+
+    #[test]
+    fn unversioned_names() {
+        assert_eq!(unversioned("python3.11"), "python");
+        assert_eq!(unversioned("python3"), "python");
+        assert_eq!(unversioned("ruby-2.7"), "ruby");
+        assert_eq!(unversioned("bash"), "bash");
+    }
+}
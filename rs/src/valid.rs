@@ -2,78 +2,167 @@
 // License: GPLv3
 
 use crate::config::Config;
-use crate::consts;
+use crate::lang;
 use std::{
-    collections::HashSet,
+    fmt,
     path::{Component, Path},
 };
-use walkdir::DirEntry;
 
-pub fn is_valid_entry(entry: &DirEntry, config: &Config) -> bool {
-    if entry.file_type().is_dir() {
-        is_valid_dir(entry.path(), config)
-    } else {
-        is_valid_file(entry.path(), config)
+// Why a file or folder is or isn't counted (or searched)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Included(String), // The include name or pattern that matched
+    Recognized(Vec<String>), // The langs claiming the file's name or ext
+    Searched,         // A folder that isn't excluded
+    Hidden(String),   // The hidden file or folder name
+    Excluded(String), // The exclude name or pattern that matched
+    NotCounted(Vec<String>), // Recognized, but not in the langs to count
+    Unknown,          // No language claims the file's name or extension
+}
+
+impl Verdict {
+    pub fn is_valid(&self) -> bool {
+        matches!(
+            self,
+            Verdict::Included(_)
+                | Verdict::Recognized(_)
+                | Verdict::Searched
+        )
     }
 }
 
-pub fn is_valid_file(filename: &Path, config: &Config) -> bool {
-    if let Some(name) = filename.file_name() {
-        if let Some(name) = name.to_str() {
-            if config.include.contains(name) {
-                return true;
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Verdict::Included(pattern) => {
+                write!(f, "included by {pattern}")
             }
-            if name.starts_with('.') {
-                return false;
+            Verdict::Recognized(langs) => {
+                write!(f, "recognized as {}", langs.join(" or "))
             }
-            let parts: HashSet<String> = filename
-                .components()
-                .filter_map(|c| match c {
-                    Component::Normal(s) => {
-                        s.to_str().map(|s| s.to_string())
-                    }
-                    _ => None,
-                })
-                .collect();
-            if parts.intersection(&config.exclude).count() > 0 {
-                return false;
+            Verdict::Searched => write!(f, "searched"),
+            Verdict::Hidden(name) => write!(f, "hidden ({name})"),
+            Verdict::Excluded(pattern) => {
+                write!(f, "excluded by {pattern}")
             }
-            for part in parts {
-                if part.len() > 1 && part.starts_with('.') {
-                    return false;
-                }
+            Verdict::NotCounted(langs) => {
+                write!(f, "language not counted ({})", langs.join(" "))
             }
-            if let Some((_, ext)) = name.rsplit_once('.') {
-                for lang in &config.langs {
-                    if consts::DATA_FOR_LANG.get()[lang.as_str()]
-                        .exts
-                        .contains(ext)
-                    {
-                        return true;
-                    }
-                }
-            } else {
-                return false; // No extension and not in includes
+            Verdict::Unknown => {
+                write!(f, "no language matches its name or extension")
             }
         }
     }
-    false
 }
 
-pub fn is_valid_dir(dirname: &Path, config: &Config) -> bool {
-    if let Some(name) = dirname.file_name() {
-        if let Some(name) = name.to_str() {
-            if name.len() > 1 && name.starts_with('.') {
-                return false;
-            }
+pub fn is_valid_dir(dirname: &Path, root: &Path, config: &Config) -> bool {
+    dir_verdict(dirname, root, config).is_valid()
+}
+
+// Patterns and excluded names are matched against the path relative to the
+// root that was searched (or to the file's folder for files given
+// explicitly), so folders above the root (e.g., a build folder that holds a
+// project) don't matter. The rules are applied in order and the first that
+// applies decides.
+pub fn file_verdict(
+    filename: &Path,
+    root: &Path,
+    config: &Config,
+) -> Verdict {
+    let relpath = filename.strip_prefix(root).unwrap_or(filename);
+    let Some(name) = filename.file_name().and_then(|name| name.to_str())
+    else {
+        return Verdict::Unknown;
+    };
+    if let Some(pattern) = include_match(name, relpath, config) {
+        return Verdict::Included(pattern);
+    }
+    // Dot files that a language claims by name (e.g., .bashrc) aren't
+    // hidden, though they still are in hidden folders
+    if name.starts_with('.') && lang::langs_for_whole_name(name).is_empty()
+    {
+        return Verdict::Hidden(name.to_string());
+    }
+    if let Some(pattern) = config.exclude_patterns.matching(relpath) {
+        return Verdict::Excluded(pattern.to_string());
+    }
+    if let Some(part) = excluded_part(relpath, config) {
+        return Verdict::Excluded(part);
+    }
+    if let Some(part) = relpath.parent().and_then(hidden_part) {
+        return Verdict::Hidden(part);
+    }
+    // Files without a recognized name or extension are only counted if
+    // included
+    let langs = lang::langs_for_name(filename);
+    if langs.is_empty() {
+        return Verdict::Unknown;
+    }
+    let (counted, not_counted): (Vec<&str>, Vec<&str>) =
+        langs.iter().partition(|lang| config.langs.contains(**lang));
+    if counted.is_empty() {
+        Verdict::NotCounted(to_strings(&not_counted))
+    } else {
+        Verdict::Recognized(to_strings(&counted))
+    }
+}
+
+pub fn dir_verdict(
+    dirname: &Path,
+    root: &Path,
+    config: &Config,
+) -> Verdict {
+    if let Some(name) = dirname.file_name().and_then(|name| name.to_str()) {
+        if name.len() > 1 && name.starts_with('.') {
+            return Verdict::Hidden(name.to_string());
         }
     }
-    if let Some(parent) = dirname.parent() {
-        if let Some(name) = parent.to_str() {
-            if config.exclude.contains(name) {
-                return false;
+    if let Ok(relpath) = dirname.strip_prefix(root) {
+        if !relpath.as_os_str().is_empty() {
+            if let Some(pattern) = config.exclude_patterns.matching(relpath)
+            {
+                return Verdict::Excluded(pattern.to_string());
+            }
+            // Checking the folder itself as well as its parents means an
+            // excluded folder isn't even entered
+            if let Some(part) = excluded_part(relpath, config) {
+                return Verdict::Excluded(part);
             }
         }
     }
-    true
+    Verdict::Searched
+}
+
+pub fn include_match(
+    name: &str,
+    relpath: &Path,
+    config: &Config,
+) -> Option<String> {
+    if config.include.contains(name) {
+        Some(name.to_string())
+    } else {
+        config.include_patterns.matching(relpath).map(|p| p.to_string())
+    }
+}
+
+// Returns the first folder or file name in relpath that is excluded
+pub fn excluded_part(relpath: &Path, config: &Config) -> Option<String> {
+    normal_parts(relpath).find(|part| config.exclude.contains(part))
+}
+
+// Returns the first folder or file name in relpath that is hidden
+pub fn hidden_part(relpath: &Path) -> Option<String> {
+    normal_parts(relpath)
+        .find(|part| part.len() > 1 && part.starts_with('.'))
+}
+
+fn normal_parts(path: &Path) -> impl Iterator<Item = String> + '_ {
+    path.components().filter_map(|c| match c {
+        Component::Normal(s) => s.to_str().map(|s| s.to_string()),
+        _ => None,
+    })
+}
+
+fn to_strings(langs: &[&str]) -> Vec<String> {
+    langs.iter().map(|lang| lang.to_string()).collect()
 }
@@ -0,0 +1,58 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use crate::config::Config;
+use crate::consts;
+use crate::count;
+use crate::lang;
+use crate::types::FileData;
+use ignore::WalkBuilder;
+use std::path::Path;
+
+pub fn get_walker(dirname: &Path, config: &Config) -> WalkBuilder {
+    let mut walker = WalkBuilder::new(dirname);
+    // We apply our own rules for . files and folders in valid.rs
+    walker.standard_filters(!config.no_ignore).hidden(false);
+    if !config.no_ignore {
+        walker.add_custom_ignore_filename(consts::IGNORE_FILENAME);
+    }
+    walker
+}
+
+// Files and git blobs are both counted from their bytes; we count on raw
+// bytes so text in any ASCII-compatible encoding is ok
+pub fn count_bytes(
+    filename: &Path,
+    name: &str,
+    root: usize,
+    bytes: &[u8],
+    config: &Config,
+) -> FileData {
+    let lang = lang::lang_for_file(filename, bytes, &config.langs);
+    let counts = count::count_lines(bytes, lang::lang_data_for(&lang));
+    FileData::new(lang, filename, name, root, counts)
+}
+
+// Returns the absolute filename, or if --relative, the filename relative to
+// the root it was found under, prefixed by the root as given if there's more
+// than one root (in which case files given explicitly are named as given)
+pub fn report_name(
+    filename: &Path,
+    root: &Path,
+    index: usize,
+    explicit: bool,
+    config: &Config,
+) -> String {
+    if !config.relative {
+        return filename.to_string_lossy().to_string();
+    }
+    let relpath = filename.strip_prefix(root).unwrap_or(filename);
+    let label = &config.files[index];
+    if config.files.len() == 1 {
+        relpath.to_string_lossy().to_string()
+    } else if explicit {
+        label.to_string()
+    } else {
+        Path::new(label).join(relpath).to_string_lossy().to_string()
+    }
+}
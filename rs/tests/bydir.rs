@@ -0,0 +1,27 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use std::{fs, process::Command};
+
+// A folder with a single file has labels narrower than the width needed to
+// elide them
+#[test]
+fn by_dir_single_file_folder() {
+    let dirname = std::env::temp_dir()
+        .join(format!("clc-bydir-{}", std::process::id()));
+    fs::create_dir_all(&dirname).unwrap();
+    fs::write(dirname.join("a.go"), "package main\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_clc"))
+        .args(["--no-config", "--by-dir", "-m", "80", "."])
+        .current_dir(&dirname)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dirname).unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("Go"), "{stdout}");
+}